@@ -0,0 +1,76 @@
+use crate::{Error, ExitStatus, ProcessInfo, PtyProcess, Result, Signal, TerminalSize};
+
+/// The operations a terminal component needs from the process it is connected to.
+///
+/// [`PtyProcess`] is the native implementation, [`MemoryPty`](crate::MemoryPty) a scripted one for tests.
+/// Output is not part of the trait, it is delivered through the `mpsc::Receiver` returned when creating a backend.
+pub trait PtyBackend: std::fmt::Debug + Send + Sync + 'static {
+    fn try_write(&self, content: Vec<u8>) -> Result<()>;
+
+    fn write(&self, content: Vec<u8>) -> impl Future<Output = Result<()>> + Send;
+
+    fn try_resize(&self, size: TerminalSize) -> Result<()>;
+
+    fn resize(&self, size: TerminalSize) -> impl Future<Output = Result<()>> + Send;
+
+    /// Resolves once the process has exited.
+    /// Returns `None` if the exit status could not be determined.
+    fn exited(&self) -> impl Future<Output = Option<ExitStatus>> + Send + 'static;
+
+    /// Takes the error which caused the output to stop, if there was one.
+    fn take_error(&self) -> Option<Error>;
+
+    fn signal(&self, signal: Signal) -> Result<()>;
+
+    fn kill(&self) -> Result<()>;
+
+    fn foreground_process(&self) -> Option<ProcessInfo>;
+
+    /// Environment variables of the process which are written into the header of recordings,
+    /// e.g. its `SHELL` and `TERM`.
+    fn recording_env(&self) -> Vec<(String, String)> {
+        Vec::new()
+    }
+}
+
+impl PtyBackend for PtyProcess {
+    fn try_write(&self, content: Vec<u8>) -> Result<()> {
+        PtyProcess::try_write(self, content)
+    }
+
+    fn write(&self, content: Vec<u8>) -> impl Future<Output = Result<()>> + Send {
+        PtyProcess::write(self, content)
+    }
+
+    fn try_resize(&self, size: TerminalSize) -> Result<()> {
+        PtyProcess::try_resize(self, size)
+    }
+
+    fn resize(&self, size: TerminalSize) -> impl Future<Output = Result<()>> + Send {
+        PtyProcess::resize(self, size)
+    }
+
+    fn exited(&self) -> impl Future<Output = Option<ExitStatus>> + Send + 'static {
+        PtyProcess::exited(self)
+    }
+
+    fn take_error(&self) -> Option<Error> {
+        PtyProcess::take_error(self)
+    }
+
+    fn signal(&self, signal: Signal) -> Result<()> {
+        PtyProcess::signal(self, signal)
+    }
+
+    fn kill(&self) -> Result<()> {
+        PtyProcess::kill(self)
+    }
+
+    fn foreground_process(&self) -> Option<ProcessInfo> {
+        PtyProcess::foreground_process(self)
+    }
+
+    fn recording_env(&self) -> Vec<(String, String)> {
+        PtyProcess::recording_env(self).to_vec()
+    }
+}
@@ -0,0 +1,218 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::mpsc;
+
+/// Full reset, sent to a subscriber which missed output before the replay, so it doesn't show a garbled screen.
+const RESET: &[u8] = b"\x1bc";
+
+/// Capacity of the channel of each subscriber, the same as the one returned by [`PtyProcess::spawn`](crate::PtyProcess::spawn).
+const SUBSCRIBER_CAPACITY: usize = 100;
+
+/// Distributes the output of a process to any number of subscribers.
+///
+/// Subscribers can be added at any time and receive the output from then on,
+/// preceded by the recent output if a replay buffer is kept.
+///
+/// A subscriber whose channel is full misses output, so a stalled subscriber can't hold up the process or the others.
+/// Once it reads again, it receives a terminal reset followed by the replay and then the output from there on.
+/// Without a replay buffer its screen stays empty until the application redraws it.
+#[derive(Debug, Clone)]
+pub struct OutputBroadcast {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    subscribers: Vec<Subscriber>,
+    replay: VecDeque<u8>,
+    replay_len: usize,
+    // the output has ended
+    finished: bool,
+}
+
+#[derive(Debug)]
+struct Subscriber {
+    sender: mpsc::Sender<Vec<u8>>,
+    // output is skipped until the subscriber caught up with the replay
+    lagging: bool,
+}
+
+impl OutputBroadcast {
+    /// Takes over the output of a process, which from now on is only received by subscribers.
+    ///
+    /// Has to be called from within a tokio runtime.
+    pub fn new(output: mpsc::Receiver<Vec<u8>>) -> Self {
+        Self::with_replay(output, 0)
+    }
+
+    /// Like [`OutputBroadcast::new`], but keeps the last `replay_len` bytes of output for new subscribers.
+    pub fn with_replay(mut output: mpsc::Receiver<Vec<u8>>, replay_len: usize) -> Self {
+        let state = Arc::new(Mutex::new(State {
+            replay_len,
+            ..State::default()
+        }));
+
+        let pump_state = state.clone();
+        tokio::spawn(async move {
+            while let Some(chunk) = output.recv().await {
+                let mut state = pump_state.lock().unwrap();
+                state.push_replay(&chunk);
+                // also removes subscribers which are gone
+                state.subscribers.retain_mut(|subscriber| {
+                    if subscriber.lagging {
+                        return true;
+                    }
+
+                    match subscriber.sender.try_send(chunk.clone()) {
+                        Ok(()) => true,
+                        Err(mpsc::error::TrySendError::Full(_)) => {
+                            subscriber.lagging = true;
+                            tokio::spawn(catch_up(pump_state.clone(), subscriber.sender.clone()));
+                            true
+                        }
+                        Err(mpsc::error::TrySendError::Closed(_)) => false,
+                    }
+                });
+            }
+
+            // the subscribers see the end of the output once their senders are gone
+            let mut state = pump_state.lock().unwrap();
+            state.subscribers.clear();
+            state.finished = true;
+        });
+
+        Self { state }
+    }
+
+    /// Returns a receiver for the output from now on, starting with the replay.
+    ///
+    /// If the output has already ended, the receiver only gets the replay.
+    pub fn subscribe(&self) -> mpsc::Receiver<Vec<u8>> {
+        let (sender, receiver) = mpsc::channel(SUBSCRIBER_CAPACITY);
+
+        let mut state = self.state.lock().unwrap();
+        if !state.replay.is_empty() {
+            let replay = state.replay.make_contiguous().to_vec();
+            // can't fail, the channel is empty
+            let _ = sender.try_send(replay);
+        }
+
+        if !state.finished {
+            state.subscribers.push(Subscriber {
+                sender,
+                lagging: false,
+            });
+        }
+
+        receiver
+    }
+
+    /// Returns the number of subscribers which are still receiving output.
+    pub fn subscribers(&self) -> usize {
+        self.state
+            .lock()
+            .unwrap()
+            .subscribers
+            .iter()
+            .filter(|subscriber| !subscriber.sender.is_closed())
+            .count()
+    }
+}
+
+/// Sends the reset and the replay once the lagging subscriber has room again.
+async fn catch_up(state: Arc<Mutex<State>>, sender: mpsc::Sender<Vec<u8>>) {
+    // the subscriber is gone
+    let Ok(permit) = sender.reserve().await else {
+        return;
+    };
+
+    // the output is skipped until here, so nothing is sent twice or out of order
+    let mut state = state.lock().unwrap();
+    permit.send([RESET, state.replay.make_contiguous()].concat());
+
+    if let Some(subscriber) = state
+        .subscribers
+        .iter_mut()
+        .find(|subscriber| subscriber.sender.same_channel(&sender))
+    {
+        subscriber.lagging = false;
+    }
+}
+
+impl State {
+    fn push_replay(&mut self, chunk: &[u8]) {
+        if self.replay_len == 0 {
+            return;
+        }
+
+        self.replay.extend(chunk);
+        let excess = self.replay.len().saturating_sub(self.replay_len);
+        self.replay.drain(..excess);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn slow_subscribers_catch_up() {
+        let (output_send, output) = mpsc::channel(1);
+        let broadcast = OutputBroadcast::with_replay(output, 4);
+
+        let mut stalled = broadcast.subscribe();
+        let mut active = broadcast.subscribe();
+
+        // a burst of output the stalled subscriber has no room for
+        for index in 0..2 * SUBSCRIBER_CAPACITY {
+            output_send.send(vec![index as u8]).await.unwrap();
+            assert_eq!(active.recv().await.unwrap(), [index as u8]);
+        }
+        assert_eq!(broadcast.subscribers(), 2);
+
+        // the stalled subscriber gets what fit into its channel, then the reset and the replay
+        for index in 0..SUBSCRIBER_CAPACITY {
+            assert_eq!(stalled.recv().await.unwrap(), [index as u8]);
+        }
+        assert_eq!(
+            stalled.recv().await.unwrap(),
+            [RESET, &[196, 197, 198, 199]].concat()
+        );
+
+        // and the output from there on
+        output_send.send(vec![200]).await.unwrap();
+        assert_eq!(active.recv().await.unwrap(), [200]);
+        assert_eq!(stalled.recv().await.unwrap(), [200]);
+
+        drop(output_send);
+        assert!(active.recv().await.is_none());
+        assert!(stalled.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn late_subscribers_get_the_replay() {
+        let (output_send, output) = mpsc::channel(1);
+        let broadcast = OutputBroadcast::with_replay(output, 4);
+
+        let mut early = broadcast.subscribe();
+        for chunk in [&b"abc"[..], b"def"] {
+            output_send.send(chunk.to_vec()).await.unwrap();
+            assert_eq!(early.recv().await.unwrap(), chunk);
+        }
+
+        let mut late = broadcast.subscribe();
+        assert_eq!(late.recv().await.unwrap(), b"cdef");
+
+        drop(output_send);
+        assert!(early.recv().await.is_none());
+        assert!(late.recv().await.is_none());
+
+        // once the output ended, only the replay is left
+        let mut finished = broadcast.subscribe();
+        assert_eq!(finished.recv().await.unwrap(), b"cdef");
+        assert!(finished.recv().await.is_none());
+    }
+}
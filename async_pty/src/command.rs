@@ -0,0 +1,254 @@
+use std::{
+    ffi::{OsStr, OsString},
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
+
+use portable_pty::CommandBuilder;
+
+use crate::TerminalSize;
+
+/// The terminal type we emulate, exported as `TERM`.
+const TERM: &str = "xterm-256color";
+
+static SHELL: LazyLock<String> = LazyLock::new(user_shell);
+
+/// Describes a process to be started inside a pty.
+#[derive(Debug, Clone)]
+pub struct Command {
+    program: OsString,
+    args: Vec<OsString>,
+    env: Vec<(OsString, Option<OsString>)>,
+    cwd: Option<PathBuf>,
+    size: TerminalSize,
+    login: bool,
+    term_program: Option<(String, String)>,
+}
+
+impl Command {
+    pub fn new(program: impl AsRef<OsStr>) -> Self {
+        Self {
+            program: program.as_ref().to_owned(),
+            args: Vec::new(),
+            env: Vec::new(),
+            cwd: None,
+            size: TerminalSize::default(),
+            login: false,
+            term_program: None,
+        }
+    }
+
+    /// Starts the default shell of the current user.
+    ///
+    /// On unix this is `$SHELL`, falling back to the shell in the passwd database and then `/bin/sh`.
+    pub fn shell() -> Self {
+        let command = Self::new(SHELL.as_str());
+
+        // the inherited value might not be the shell we resolved
+        #[cfg(unix)]
+        let command = command.env("SHELL", SHELL.as_str());
+
+        command
+    }
+
+    /// Starts the program as a login shell by passing `-l`, which all common unix shells understand.
+    ///
+    /// This is ignored on Windows.
+    pub fn login(mut self, login: bool) -> Self {
+        self.login = login;
+        self
+    }
+
+    /// Exports `TERM_PROGRAM` and `TERM_PROGRAM_VERSION`, so tools can detect the terminal.
+    ///
+    /// Without this, the values inherited from the terminal we were started from are removed.
+    pub fn term_program(mut self, name: impl Into<String>, version: impl Into<String>) -> Self {
+        self.term_program = Some((name.into(), version.into()));
+        self
+    }
+
+    pub fn arg(mut self, arg: impl AsRef<OsStr>) -> Self {
+        self.args.push(arg.as_ref().to_owned());
+        self
+    }
+
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.args
+            .extend(args.into_iter().map(|arg| arg.as_ref().to_owned()));
+        self
+    }
+
+    /// Sets an environment variable for the child, overriding the inherited value.
+    pub fn env(mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) -> Self {
+        self.env
+            .push((key.as_ref().to_owned(), Some(value.as_ref().to_owned())));
+        self
+    }
+
+    /// Removes an inherited environment variable from the child.
+    pub fn env_remove(mut self, key: impl AsRef<OsStr>) -> Self {
+        self.env.push((key.as_ref().to_owned(), None));
+        self
+    }
+
+    /// Sets the working directory of the child.
+    /// If this is not set, the child starts in the home directory of the user.
+    pub fn cwd(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.cwd = Some(dir.into());
+        self
+    }
+
+    /// Sets the size the pty is opened with.
+    pub fn size(mut self, size: TerminalSize) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn get_size(&self) -> &TerminalSize {
+        &self.size
+    }
+
+    pub fn get_cwd(&self) -> Option<&Path> {
+        self.cwd.as_deref()
+    }
+
+    /// Returns the value of an environment variable as the child sees it.
+    pub fn get_env(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        let key = key.as_ref();
+        if let Some((_, value)) = self.env.iter().rev().find(|(name, _)| name == key) {
+            return value.clone();
+        }
+
+        match key.to_str() {
+            Some("TERM") => Some(TERM.into()),
+            Some("COLORTERM") => Some("truecolor".into()),
+            Some("TERM_PROGRAM") => self.term_program.as_ref().map(|(name, _)| name.into()),
+            Some("TERM_PROGRAM_VERSION") => self
+                .term_program
+                .as_ref()
+                .map(|(_, version)| version.into()),
+            _ => std::env::var_os(key),
+        }
+    }
+
+    pub(crate) fn to_builder(&self) -> CommandBuilder {
+        let mut builder = CommandBuilder::new(&self.program);
+        #[cfg(unix)]
+        if self.login {
+            builder.arg("-l");
+        }
+        builder.args(&self.args);
+
+        // set before the custom environment, so these can still be overridden
+        builder.env("TERM", TERM);
+        builder.env("COLORTERM", "truecolor");
+        match &self.term_program {
+            Some((name, version)) => {
+                builder.env("TERM_PROGRAM", name);
+                builder.env("TERM_PROGRAM_VERSION", version);
+            }
+            None => {
+                builder.env_remove("TERM_PROGRAM");
+                builder.env_remove("TERM_PROGRAM_VERSION");
+            }
+        }
+
+        for (key, value) in &self.env {
+            match value {
+                Some(value) => builder.env(key, value),
+                None => builder.env_remove(key),
+            }
+        }
+
+        if let Some(cwd) = &self.cwd {
+            builder.cwd(cwd);
+        }
+
+        builder
+    }
+}
+
+#[cfg(unix)]
+fn user_shell() -> String {
+    if let Some(shell) = std::env::var("SHELL")
+        .ok()
+        .filter(|shell| is_executable(shell))
+    {
+        return shell;
+    }
+
+    if let Some(shell) = passwd_shell().filter(|shell| is_executable(shell)) {
+        return shell;
+    }
+
+    "/bin/sh".to_string()
+}
+
+#[cfg(unix)]
+fn is_executable(path: &str) -> bool {
+    std::ffi::CString::new(path)
+        .is_ok_and(|path| unsafe { libc::access(path.as_ptr(), libc::X_OK) } == 0)
+}
+
+#[cfg(unix)]
+fn passwd_shell() -> Option<String> {
+    let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut buffer = vec![0 as libc::c_char; 4096];
+    let mut result = std::ptr::null_mut();
+
+    let status = unsafe {
+        libc::getpwuid_r(
+            libc::getuid(),
+            &mut passwd,
+            buffer.as_mut_ptr(),
+            buffer.len(),
+            &mut result,
+        )
+    };
+    if status != 0 || result.is_null() || passwd.pw_shell.is_null() {
+        return None;
+    }
+
+    let shell = unsafe { std::ffi::CStr::from_ptr(passwd.pw_shell) };
+    shell
+        .to_str()
+        .ok()
+        .filter(|shell| !shell.is_empty())
+        .map(str::to_owned)
+}
+
+#[cfg(not(unix))]
+fn user_shell() -> String {
+    let shell = CommandBuilder::new_default_prog().get_shell();
+    if &shell == r"C:\WINDOWS\system32\cmd.exe" {
+        return "powershell.exe".to_string();
+    }
+
+    shell
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn child_environment() {
+        let command = Command::new("sh");
+        assert_eq!(command.get_env("TERM").unwrap(), TERM);
+        assert_eq!(command.get_env("TERM_PROGRAM"), None);
+
+        let command = command
+            .env("TERM", "dumb")
+            .env("SHELL", "/bin/custom")
+            .env_remove("COLORTERM")
+            .term_program("frozen_term", "1.0");
+        assert_eq!(command.get_env("TERM").unwrap(), "dumb");
+        assert_eq!(command.get_env("SHELL").unwrap(), "/bin/custom");
+        assert_eq!(command.get_env("COLORTERM"), None);
+        assert_eq!(command.get_env("TERM_PROGRAM").unwrap(), "frozen_term");
+    }
+}
@@ -0,0 +1,33 @@
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("failed to spawn process: {0}")]
+    Spawn(#[source] BoxError),
+    #[error("failed to write to pty: {0}")]
+    Write(#[source] std::io::Error),
+    #[error("failed to read from pty: {0}")]
+    Read(#[source] std::io::Error),
+    #[error("failed to resize pty: {0}")]
+    Resize(#[source] BoxError),
+    #[error("failed to send signal: {0}")]
+    Signal(#[source] std::io::Error),
+    #[error("signals are not supported on this platform")]
+    SignalsUnsupported,
+    #[error("input channel is full")]
+    ChannelFull,
+    #[error("child process is gone")]
+    ChildGone,
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl Error {
+    pub(crate) fn spawn(err: impl Into<BoxError>) -> Self {
+        Self::Spawn(err.into())
+    }
+
+    pub(crate) fn resize(err: impl Into<BoxError>) -> Self {
+        Self::Resize(err.into())
+    }
+}
@@ -1,34 +1,101 @@
-use std::sync::LazyLock;
+use std::sync::{Arc, Mutex};
 
-use anyhow::Result;
-use portable_pty::{CommandBuilder, PtySize, native_pty_system};
+use portable_pty::{Child, ChildKiller, MasterPty, PtySize, native_pty_system};
 use serde::{Deserialize, Serialize};
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, watch};
 use tokio_util::sync::CancellationToken;
 
-static SHELL: LazyLock<String> = LazyLock::new(|| {
-    let shell = CommandBuilder::new_default_prog().get_shell();
-    if &shell == r"C:\WINDOWS\system32\cmd.exe" {
-        return "powershell.exe".to_string();
-    }
+mod backend;
+mod broadcast;
+mod command;
+mod error;
+mod memory;
+mod paste;
+mod pool;
+mod process;
+mod recording;
+#[cfg(feature = "remote")]
+pub mod remote;
+mod signal;
+mod stream;
+#[cfg(unix)]
+mod unix;
 
-    shell
-});
+pub use backend::PtyBackend;
+pub use broadcast::OutputBroadcast;
+pub use command::Command;
+pub use error::{Error, Result};
+pub use memory::{MemoryPty, MemoryPtyController};
+pub use paste::{PASTE_CHUNK_LEN, Paste, PasteHandle};
+pub use pool::ShellPool;
+pub use portable_pty::ExitStatus;
+pub use process::ProcessInfo;
+pub use recording::{Cast, CastEvent, CastEventKind, Recorder};
+pub use signal::Signal;
+pub use stream::{PtyStream, ResizeHandle};
 
+#[derive(Debug, Clone)]
 pub enum TerminalInput {
     Input(Vec<u8>),
     Resize(TerminalSize),
 }
 
-#[derive(Debug)]
+/// Size of the buffer output is read into.
+const READ_BUFFER_LEN: usize = 64 * 1024;
+
+/// A process running inside a pty.
+///
+/// Spawning outside of a tokio runtime fails with [`Error::Spawn`].
+/// On unix the pty is driven by the tokio reactor, so like all tokio io types it panics if the runtime has io disabled.
+/// If the pty can't be registered, e.g. because the platform can't poll it, it falls back to blocking threads.
+///
+/// Dropping the process hangs up the pty, which terminates the child process.
 pub struct PtyProcess {
     write: mpsc::Sender<TerminalInput>,
+    exit: watch::Receiver<Option<ExitStatus>>,
+    master: Arc<Mutex<Box<dyn MasterPty + Send>>>,
+    killer: Mutex<Box<dyn ChildKiller + Send + Sync>>,
+    pid: Option<u32>,
+    /// the first error encountered by the io threads
+    failure: Arc<Mutex<Option<Error>>>,
+    /// stops the io once the process is dropped
+    io: CancellationToken,
+    /// `SHELL` and `TERM` as the child sees them
+    recording_env: Vec<(String, String)>,
+}
+
+impl std::fmt::Debug for PtyProcess {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PtyProcess")
+            .field("pid", &self.pid)
+            .field("exit", &*self.exit.borrow())
+            .finish()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TerminalSize {
     pub cols: u16,
     pub rows: u16,
+    /// Width of the text area in pixels, reported to applications via `TIOCGWINSZ`.
+    /// Zero if unknown.
+    #[serde(default)]
+    pub pixel_width: u16,
+    /// Height of the text area in pixels, reported to applications via `TIOCGWINSZ`.
+    /// Zero if unknown.
+    #[serde(default)]
+    pub pixel_height: u16,
+}
+
+impl Default for TerminalSize {
+    fn default() -> Self {
+        Self {
+            cols: 80,
+            rows: 24,
+            pixel_width: 0,
+            pixel_height: 0,
+        }
+    }
 }
 
 impl From<TerminalSize> for PtySize {
@@ -36,128 +103,459 @@ impl From<TerminalSize> for PtySize {
         Self {
             rows: value.rows,
             cols: value.cols,
-            ..Default::default()
+            pixel_width: value.pixel_width,
+            pixel_height: value.pixel_height,
         }
     }
 }
 
 impl PtyProcess {
+    /// Starts the default shell of the current user.
     pub async fn shell(size: TerminalSize) -> Result<(Self, mpsc::Receiver<Vec<u8>>)> {
-        tokio::task::spawn_blocking(move || {
-            let pty_system = native_pty_system();
+        Self::spawn(Command::shell().size(size)).await
+    }
 
-            let pair = pty_system.openpty(size.into())?;
+    pub async fn spawn(command: Command) -> Result<(Self, mpsc::Receiver<Vec<u8>>)> {
+        // the io is driven by tasks, which can't be spawned outside of a runtime
+        tokio::runtime::Handle::try_current().map_err(Error::spawn)?;
+
+        let recording_env = ["SHELL", "TERM"]
+            .into_iter()
+            .filter_map(|key| Some((key.to_string(), command.get_env(key)?.into_string().ok()?)))
+            .collect();
+
+        let (master, child) = tokio::task::spawn_blocking(move || {
+            let pty_system = native_pty_system();
 
-            let shell_cmd = CommandBuilder::new(SHELL.to_owned());
+            let pair = pty_system
+                .openpty(command.get_size().clone().into())
+                .map_err(Error::spawn)?;
 
-            let child = pair.slave.spawn_command(shell_cmd)?;
+            let child = pair
+                .slave
+                .spawn_command(command.to_builder())
+                .map_err(Error::spawn)?;
             drop(pair.slave);
 
-            let master = pair.master;
-            let mut reader = master.try_clone_reader()?;
-            let (writer_send, writer_recv) = mpsc::channel::<TerminalInput>(10);
-            let (helper_send, helper_recv) = mpsc::channel::<TerminalInput>(10);
-
-            let cancel = CancellationToken::new();
-            let cancel2 = cancel.clone();
-
-            tokio::spawn(async move {
-                let cancel = cancel2;
-                let mut writer_recv = writer_recv;
-                cancel
-                    .run_until_cancelled(async move {
-                        while let Some(input) = writer_recv.recv().await {
-                            if helper_send.send(input).await.is_err() {
-                                break;
+            Result::Ok((pair.master, child))
+        })
+        .await
+        .map_err(Error::spawn)??;
+
+        #[cfg(unix)]
+        if let Ok(pty) = unix::AsyncPty::new(&*master) {
+            return Ok(Self::start_async(master, child, pty, recording_env));
+        }
+
+        Self::start_threaded(master, child, recording_env)
+    }
+
+    /// Uses a reader and a writer task instead of threads, the child is awaited through a pidfd if possible.
+    #[cfg(unix)]
+    fn start_async(
+        master: Box<dyn MasterPty + Send>,
+        child: Box<dyn Child + Send + Sync>,
+        pty: unix::AsyncPty,
+        recording_env: Vec<(String, String)>,
+    ) -> (Self, mpsc::Receiver<Vec<u8>>) {
+        let pid = child.process_id();
+        let killer = child.clone_killer();
+
+        let master = Arc::new(Mutex::new(master));
+        let failure = Arc::new(Mutex::new(None));
+        let pty = Arc::new(pty);
+        let io = CancellationToken::new();
+        // input is rejected once the child is gone, like in the threaded version
+        let exited = io.child_token();
+
+        let (writer_send, writer_recv) = mpsc::channel::<TerminalInput>(10);
+        let writer_pty = pty.clone();
+        let writer_master = master.clone();
+        let writer_failure = failure.clone();
+        let writer_cancel = exited.clone();
+        tokio::spawn(async move {
+            let mut writer_recv = writer_recv;
+            writer_cancel
+                .run_until_cancelled(async move {
+                    while let Some(input) = writer_recv.recv().await {
+                        let result = match input {
+                            TerminalInput::Input(input) => {
+                                writer_pty.write_all(&input).await.map_err(Error::Write)
                             }
+                            TerminalInput::Resize(size) => writer_master
+                                .lock()
+                                .unwrap()
+                                .resize(size.into())
+                                .map_err(Error::resize),
+                        };
+
+                        if let Err(err) = result {
+                            record_failure(&writer_failure, err);
+                            return;
                         }
-                    })
-                    .await;
-            });
-
-            // writer thread
-            std::thread::spawn(move || {
-                let mut helper_recv = helper_recv;
-                if let Ok(mut writer) = master.take_writer() {
-                    while let Some(input) = helper_recv.blocking_recv() {
-                        match input {
-                            TerminalInput::Input(input) => {
-                                if let Err(_err) = writer.write_all(&input) {
-                                    return;
+                    }
+                })
+                .await;
+        });
+
+        let (reader_send, reader_recv) = mpsc::channel::<Vec<u8>>(100);
+        let reader_failure = failure.clone();
+        let reader_cancel = io.clone();
+        tokio::spawn(async move {
+            reader_cancel
+                .run_until_cancelled(async move {
+                    let mut buffer = Vec::with_capacity(READ_BUFFER_LEN);
+                    loop {
+                        match pty.read(&mut buffer).await {
+                            Ok(0) => break,
+                            Ok(_) => {
+                                if reader_send.send(take_output(&mut buffer)).await.is_err() {
+                                    break;
                                 }
                             }
-                            TerminalInput::Resize(size) => {
-                                if let Err(_err) = master.resize(size.into()) {
-                                    return;
+                            Err(err) => {
+                                // linux reports EIO once the child has closed the pty
+                                if !is_hangup(&err) {
+                                    record_failure(&reader_failure, Error::Read(err));
                                 }
+                                break;
                             }
                         }
                     }
-                }
-            });
-
-            let (reader_send, reader_recv) = mpsc::channel::<Vec<u8>>(100);
-
-            // reader thread
-            std::thread::spawn(move || {
-                let mut buffer = [0u8; 1024];
-                loop {
-                    match reader.read(&mut buffer) {
-                        Ok(0) => break,
-                        Ok(bytes) => {
-                            if bytes == 0 {
-                                break;
-                            }
+                })
+                .await;
+        });
 
-                            let mut chunk = Vec::new();
-                            chunk.extend_from_slice(&buffer[0..bytes]);
-                            if let Err(_err) = reader_send.blocking_send(chunk) {
-                                break;
-                            }
+        let (exit_send, exit_recv) = watch::channel(None);
+        tokio::spawn(async move {
+            // if waiting fails, the sender is dropped without a status
+            if let Ok(status) = unix::wait(child).await {
+                let _ = exit_send.send(Some(status));
+            }
+
+            exited.cancel();
+        });
+
+        (
+            Self {
+                write: writer_send,
+                exit: exit_recv,
+                master,
+                killer: Mutex::new(killer),
+                pid,
+                failure,
+                io,
+                recording_env,
+            },
+            reader_recv,
+        )
+    }
+
+    /// Uses blocking threads for reading, writing and waiting for the child.
+    fn start_threaded(
+        master: Box<dyn MasterPty + Send>,
+        child: Box<dyn Child + Send + Sync>,
+        recording_env: Vec<(String, String)>,
+    ) -> Result<(Self, mpsc::Receiver<Vec<u8>>)> {
+        let pid = child.process_id();
+        let killer = child.clone_killer();
+
+        let mut reader = master.try_clone_reader().map_err(Error::spawn)?;
+        let writer = master.take_writer().map_err(Error::spawn)?;
+        let master = Arc::new(Mutex::new(master));
+        let failure = Arc::new(Mutex::new(None));
+        let (writer_send, writer_recv) = mpsc::channel::<TerminalInput>(10);
+        let (helper_send, helper_recv) = mpsc::channel::<TerminalInput>(10);
+
+        let io = CancellationToken::new();
+        let cancel = io.child_token();
+        let cancel2 = cancel.clone();
+
+        tokio::spawn(async move {
+            let cancel = cancel2;
+            let mut writer_recv = writer_recv;
+            cancel
+                .run_until_cancelled(async move {
+                    while let Some(input) = writer_recv.recv().await {
+                        if helper_send.send(input).await.is_err() {
+                            break;
                         }
-                        Err(_err) => {
+                    }
+                })
+                .await;
+        });
+
+        // writer thread
+        let writer_master = master.clone();
+        let writer_failure = failure.clone();
+        std::thread::spawn(move || {
+            let mut helper_recv = helper_recv;
+            let mut writer = writer;
+            while let Some(input) = helper_recv.blocking_recv() {
+                let result = match input {
+                    TerminalInput::Input(input) => writer.write_all(&input).map_err(Error::Write),
+                    TerminalInput::Resize(size) => writer_master
+                        .lock()
+                        .unwrap()
+                        .resize(size.into())
+                        .map_err(Error::resize),
+                };
+
+                if let Err(err) = result {
+                    record_failure(&writer_failure, err);
+                    return;
+                }
+            }
+        });
+
+        let (reader_send, reader_recv) = mpsc::channel::<Vec<u8>>(100);
+
+        // reader thread
+        let reader_failure = failure.clone();
+        std::thread::spawn(move || {
+            let mut buffer = vec![0u8; READ_BUFFER_LEN];
+            loop {
+                match reader.read(&mut buffer) {
+                    Ok(0) => break,
+                    Ok(bytes) => {
+                        buffer.truncate(bytes);
+                        if let Err(_err) = reader_send.blocking_send(take_output(&mut buffer)) {
                             break;
                         }
+                        buffer.resize(READ_BUFFER_LEN, 0);
+                    }
+                    Err(err) => {
+                        // linux reports EIO once the child has closed the pty
+                        if !is_hangup(&err) {
+                            record_failure(&reader_failure, Error::Read(err));
+                        }
+                        break;
                     }
                 }
-            });
+            }
+        });
 
-            // For win specifically, the explicit child does implement Future.
-            // Unfortunately, it doesn't work.
-            // As I'm not all that great of a programmer, this is what I'll use for now.
-            std::thread::spawn(move || {
-                let mut child = child;
-                let _ = child.wait();
+        let (exit_send, exit_recv) = watch::channel(None);
 
-                cancel.cancel();
-            });
+        // For win specifically, the explicit child does implement Future.
+        // Unfortunately, it doesn't work.
+        // As I'm not all that great of a programmer, this is what I'll use for now.
+        std::thread::spawn(move || {
+            let mut child = child;
+            // if waiting fails, the sender is dropped without a status
+            if let Ok(status) = child.wait() {
+                let _ = exit_send.send(Some(status));
+            }
 
-            Ok((Self { write: writer_send }, reader_recv))
-        })
-        .await?
+            cancel.cancel();
+        });
+
+        Ok((
+            Self {
+                write: writer_send,
+                exit: exit_recv,
+                master,
+                killer: Mutex::new(killer),
+                pid,
+                failure,
+                io,
+                recording_env,
+            },
+            reader_recv,
+        ))
     }
 
-    pub async fn resize(&self, size: TerminalSize) -> Result<()> {
-        self.write.send(TerminalInput::Resize(size)).await?;
+    /// Resolves once the child process has exited.
+    ///
+    /// Returns `None` if the exit status could not be determined.
+    /// The returned future does not borrow the process, so it can be moved into a task.
+    pub fn exited(&self) -> impl Future<Output = Option<ExitStatus>> + Send + 'static {
+        let mut exit = self.exit.clone();
+        async move {
+            exit.wait_for(Option::is_some)
+                .await
+                .ok()
+                .and_then(|status| status.clone())
+        }
+    }
 
-        Ok(())
+    /// Returns the exit status if the child process has already exited.
+    pub fn try_exit_status(&self) -> Option<ExitStatus> {
+        self.exit.borrow().clone()
     }
 
-    pub fn try_resize(&self, size: TerminalSize) -> Result<()> {
-        self.write.try_send(TerminalInput::Resize(size))?;
+    /// Takes the error which caused the pty io to stop, if there was one.
+    ///
+    /// Once the output channel is closed, this can be used to check if it closed because of an error.
+    pub fn take_error(&self) -> Option<Error> {
+        self.failure.lock().unwrap().take()
+    }
 
-        Ok(())
+    /// Returns the process id of the child process.
+    pub fn pid(&self) -> Option<u32> {
+        self.pid
     }
 
-    pub async fn write(&self, content: Vec<u8>) -> Result<()> {
-        self.write.send(TerminalInput::Input(content)).await?;
+    /// Returns `SHELL` and `TERM` as the child sees them, for the header of recordings.
+    pub fn recording_env(&self) -> &[(String, String)] {
+        &self.recording_env
+    }
+
+    /// Returns the process currently in the foreground of the pty, e.g. `htop` started from the shell.
+    ///
+    /// If no other process is in the foreground, this is the child process itself.
+    pub fn foreground_process(&self) -> Option<ProcessInfo> {
+        #[cfg(unix)]
+        if let Some(group) = self.master.lock().unwrap().process_group_leader()
+            && let Some(info) = ProcessInfo::from_pid(group as u32)
+        {
+            return Some(info);
+        }
+
+        ProcessInfo::from_pid(self.pid?)
+    }
+
+    /// Forcefully terminates the child process.
+    ///
+    /// Does nothing once the child has been reaped, as its pid may already belong to another process.
+    pub fn kill(&self) -> Result<()> {
+        if self.try_exit_status().is_some() {
+            return Ok(());
+        }
+
+        #[cfg(unix)]
+        if let Some(pid) = self.pid {
+            return send_signal(pid as libc::pid_t, Signal::Kill);
+        }
+
+        self.killer.lock().unwrap().kill().map_err(Error::Signal)
+    }
+
+    /// Sends a signal to the foreground process group of the pty.
+    ///
+    /// This is the group which would receive the signal if the user pressed e.g. Ctrl+C.
+    /// If the foreground process group can't be determined, the signal is sent to the child process instead.
+    #[cfg(unix)]
+    pub fn signal(&self, signal: Signal) -> Result<()> {
+        let group = self.master.lock().unwrap().process_group_leader();
 
-        Ok(())
+        match (group, self.pid) {
+            (Some(group), _) => send_signal(-group, signal),
+            // the pid may have been reused once the child was reaped
+            (None, Some(pid)) if self.try_exit_status().is_none() => {
+                send_signal(pid as libc::pid_t, signal)
+            }
+            (None, _) => Err(Error::ChildGone),
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn signal(&self, _signal: Signal) -> Result<()> {
+        Err(Error::SignalsUnsupported)
+    }
+
+    pub async fn resize(&self, size: TerminalSize) -> Result<()> {
+        self.write
+            .send(TerminalInput::Resize(size))
+            .await
+            .map_err(|_| self.closed_error())
+    }
+
+    pub fn try_resize(&self, size: TerminalSize) -> Result<()> {
+        self.write
+            .try_send(TerminalInput::Resize(size))
+            .map_err(|err| self.try_send_error(err))
+    }
+
+    pub async fn write(&self, content: Vec<u8>) -> Result<()> {
+        self.write
+            .send(TerminalInput::Input(content))
+            .await
+            .map_err(|_| self.closed_error())
     }
 
     pub fn try_write(&self, content: Vec<u8>) -> Result<()> {
-        self.write.try_send(TerminalInput::Input(content))?;
+        self.write
+            .try_send(TerminalInput::Input(content))
+            .map_err(|err| self.try_send_error(err))
+    }
+
+    /// The input channel only closes if the writer failed or the child is gone.
+    fn closed_error(&self) -> Error {
+        self.take_error().unwrap_or(Error::ChildGone)
+    }
+
+    fn try_send_error<T>(&self, err: mpsc::error::TrySendError<T>) -> Error {
+        match err {
+            mpsc::error::TrySendError::Full(_) => Error::ChannelFull,
+            mpsc::error::TrySendError::Closed(_) => self.closed_error(),
+        }
+    }
+}
+
+impl Drop for PtyProcess {
+    fn drop(&mut self) {
+        self.io.cancel();
+
+        // The waiter thread reaps the child once it has exited
+        if self.exit.borrow().is_none() {
+            let _ = self.killer.lock().unwrap().kill();
+        }
+    }
+}
+
+/// Sends a signal to a process, or to a process group if `pid` is negative.
+#[cfg(unix)]
+fn send_signal(pid: libc::pid_t, signal: Signal) -> Result<()> {
+    if unsafe { libc::kill(pid, signal.as_raw()) } != 0 {
+        return Err(Error::Signal(std::io::Error::last_os_error()));
+    }
+
+    Ok(())
+}
+
+/// Only the first failure is kept, as later ones are usually caused by it.
+fn record_failure(failure: &Mutex<Option<Error>>, err: Error) {
+    let mut failure = failure.lock().unwrap();
+    if failure.is_none() {
+        *failure = Some(err);
+    }
+}
+
+fn is_hangup(err: &std::io::Error) -> bool {
+    #[cfg(unix)]
+    if err.raw_os_error() == Some(libc::EIO) {
+        return true;
+    }
+
+    err.kind() == std::io::ErrorKind::BrokenPipe
+}
+
+/// Takes the output read into `buffer`, which is reused for the next read.
+///
+/// Large chunks are handed over without copying and the buffer is replaced,
+/// small ones are copied, so the channel doesn't hold on to the whole capacity for a few bytes.
+fn take_output(buffer: &mut Vec<u8>) -> Vec<u8> {
+    if buffer.len() >= READ_BUFFER_LEN / 2 {
+        return std::mem::replace(buffer, Vec::with_capacity(READ_BUFFER_LEN));
+    }
+
+    let output = buffer.clone();
+    buffer.clear();
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use std::task::{Context, Poll, Waker};
+
+    use super::*;
+
+    #[test]
+    fn spawn_outside_of_a_runtime() {
+        let mut spawn = std::pin::pin!(PtyProcess::spawn(Command::new("sh")));
+        let poll = spawn.as_mut().poll(&mut Context::from_waker(Waker::noop()));
 
-        Ok(())
+        assert!(matches!(poll, Poll::Ready(Err(Error::Spawn(_)))));
     }
 }
@@ -0,0 +1,269 @@
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::{mpsc, watch};
+
+use crate::{
+    Error, ExitStatus, ProcessInfo, PtyBackend, Result, Signal, TerminalInput, TerminalSize,
+};
+
+/// A scripted backend which doesn't start any process.
+///
+/// Everything written to it can be inspected through the [`MemoryPtyController`],
+/// which is also used to feed output and to simulate the process exiting.
+/// This makes it possible to test terminal components deterministically.
+#[derive(Debug)]
+pub struct MemoryPty {
+    write: mpsc::Sender<TerminalInput>,
+    exit: watch::Receiver<Option<ExitStatus>>,
+    shared: Arc<Shared>,
+}
+
+/// The other end of a [`MemoryPty`].
+#[derive(Debug)]
+pub struct MemoryPtyController {
+    output: Option<mpsc::Sender<Vec<u8>>>,
+    input: mpsc::Receiver<TerminalInput>,
+    exit: watch::Sender<Option<ExitStatus>>,
+    shared: Arc<Shared>,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    signals: Mutex<Vec<Signal>>,
+    failure: Mutex<Option<Error>>,
+}
+
+impl MemoryPty {
+    /// Creates the backend, its output channel and the controller to script it.
+    pub fn new() -> (Self, mpsc::Receiver<Vec<u8>>, MemoryPtyController) {
+        // same capacities as the native pty
+        let (input_send, input_recv) = mpsc::channel(10);
+        let (output_send, output_recv) = mpsc::channel(100);
+        let (exit_send, exit_recv) = watch::channel(None);
+        let shared = Arc::new(Shared::default());
+
+        (
+            Self {
+                write: input_send,
+                exit: exit_recv,
+                shared: shared.clone(),
+            },
+            output_recv,
+            MemoryPtyController {
+                output: Some(output_send),
+                input: input_recv,
+                exit: exit_send,
+                shared,
+            },
+        )
+    }
+
+    fn check_running(&self) -> Result<()> {
+        if self.exit.borrow().is_some() {
+            return Err(Error::ChildGone);
+        }
+
+        Ok(())
+    }
+}
+
+impl PtyBackend for MemoryPty {
+    fn try_write(&self, content: Vec<u8>) -> Result<()> {
+        self.check_running()?;
+        self.write
+            .try_send(TerminalInput::Input(content))
+            .map_err(try_send_error)
+    }
+
+    async fn write(&self, content: Vec<u8>) -> Result<()> {
+        self.check_running()?;
+        self.write
+            .send(TerminalInput::Input(content))
+            .await
+            .map_err(|_| Error::ChildGone)
+    }
+
+    fn try_resize(&self, size: TerminalSize) -> Result<()> {
+        self.check_running()?;
+        self.write
+            .try_send(TerminalInput::Resize(size))
+            .map_err(try_send_error)
+    }
+
+    async fn resize(&self, size: TerminalSize) -> Result<()> {
+        self.check_running()?;
+        self.write
+            .send(TerminalInput::Resize(size))
+            .await
+            .map_err(|_| Error::ChildGone)
+    }
+
+    fn exited(&self) -> impl Future<Output = Option<ExitStatus>> + Send + 'static {
+        let mut exit = self.exit.clone();
+        async move {
+            exit.wait_for(Option::is_some)
+                .await
+                .ok()
+                .and_then(|status| status.clone())
+        }
+    }
+
+    fn take_error(&self) -> Option<Error> {
+        self.shared.failure.lock().unwrap().take()
+    }
+
+    fn signal(&self, signal: Signal) -> Result<()> {
+        self.check_running()?;
+        self.shared.signals.lock().unwrap().push(signal);
+        Ok(())
+    }
+
+    /// Only records [`Signal::Kill`], use [`MemoryPtyController::exit`] to simulate the result.
+    fn kill(&self) -> Result<()> {
+        self.signal(Signal::Kill)
+    }
+
+    fn foreground_process(&self) -> Option<ProcessInfo> {
+        None
+    }
+}
+
+impl MemoryPtyController {
+    /// Feeds output to the terminal as if the process had written it.
+    pub async fn feed(&self, output: impl Into<Vec<u8>>) -> Result<()> {
+        let output_send = self.output.as_ref().ok_or(Error::ChildGone)?;
+        output_send
+            .send(output.into())
+            .await
+            .map_err(|_| Error::ChildGone)
+    }
+
+    pub fn try_feed(&self, output: impl Into<Vec<u8>>) -> Result<()> {
+        let output_send = self.output.as_ref().ok_or(Error::ChildGone)?;
+        output_send.try_send(output.into()).map_err(try_send_error)
+    }
+
+    /// Waits for the next input or resize sent to the backend.
+    pub async fn recv(&mut self) -> Option<TerminalInput> {
+        self.input.recv().await
+    }
+
+    pub fn try_recv(&mut self) -> Option<TerminalInput> {
+        self.input.try_recv().ok()
+    }
+
+    /// Returns all signals sent to the backend so far.
+    pub fn signals(&self) -> Vec<Signal> {
+        self.shared.signals.lock().unwrap().clone()
+    }
+
+    /// Simulates the process exiting, which also closes the output.
+    pub fn exit(&mut self, status: ExitStatus) {
+        self.output = None;
+        let _ = self.exit.send(Some(status));
+    }
+
+    /// Simulates the pty failing, which closes the output without an exit status.
+    pub fn fail(&mut self, err: Error) {
+        *self.shared.failure.lock().unwrap() = Some(err);
+        self.output = None;
+    }
+}
+
+fn try_send_error<T>(err: mpsc::error::TrySendError<T>) -> Error {
+    match err {
+        mpsc::error::TrySendError::Full(_) => Error::ChannelFull,
+        mpsc::error::TrySendError::Closed(_) => Error::ChildGone,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn input_and_output() {
+        let (pty, mut output, mut controller) = MemoryPty::new();
+
+        pty.write(b"echo hi\r".to_vec()).await.unwrap();
+        pty.try_write(b"ls\r".to_vec()).unwrap();
+
+        let Some(TerminalInput::Input(input)) = controller.recv().await else {
+            panic!("expected input");
+        };
+        assert_eq!(input, b"echo hi\r");
+        let Some(TerminalInput::Input(input)) = controller.try_recv() else {
+            panic!("expected input");
+        };
+        assert_eq!(input, b"ls\r");
+
+        controller.feed("hi\r\n").await.unwrap();
+        controller.try_feed("$ ").unwrap();
+        assert_eq!(output.recv().await.unwrap(), b"hi\r\n");
+        assert_eq!(output.recv().await.unwrap(), b"$ ");
+    }
+
+    #[tokio::test]
+    async fn resize() {
+        let (pty, _output, mut controller) = MemoryPty::new();
+
+        let size = TerminalSize {
+            cols: 120,
+            rows: 40,
+            ..Default::default()
+        };
+        pty.resize(size.clone()).await.unwrap();
+        pty.try_resize(TerminalSize::default()).unwrap();
+
+        let Some(TerminalInput::Resize(received)) = controller.recv().await else {
+            panic!("expected resize");
+        };
+        assert_eq!((received.cols, received.rows), (120, 40));
+        let Some(TerminalInput::Resize(received)) = controller.recv().await else {
+            panic!("expected resize");
+        };
+        assert_eq!((received.cols, received.rows), (80, 24));
+    }
+
+    #[tokio::test]
+    async fn exit_closes_output() {
+        let (pty, mut output, mut controller) = MemoryPty::new();
+
+        pty.signal(Signal::Interrupt).unwrap();
+        pty.kill().unwrap();
+        assert_eq!(controller.signals(), [Signal::Interrupt, Signal::Kill]);
+
+        controller.exit(ExitStatus::with_exit_code(3));
+
+        assert!(output.recv().await.is_none());
+        assert_eq!(pty.exited().await.unwrap().exit_code(), 3);
+        assert!(matches!(
+            pty.try_write(b"x".to_vec()),
+            Err(Error::ChildGone)
+        ));
+        assert!(matches!(pty.signal(Signal::Kill), Err(Error::ChildGone)));
+        assert!(controller.feed("late").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn failure_closes_output() {
+        let (pty, mut output, mut controller) = MemoryPty::new();
+
+        controller.fail(Error::Read(std::io::ErrorKind::BrokenPipe.into()));
+
+        assert!(output.recv().await.is_none());
+        assert!(matches!(pty.take_error(), Some(Error::Read(_))));
+        assert!(pty.take_error().is_none());
+    }
+
+    #[test]
+    fn full_input_channel() {
+        let (pty, _output, _controller) = MemoryPty::new();
+
+        while pty.try_write(b"x".to_vec()).is_ok() {}
+        assert!(matches!(
+            pty.try_write(b"x".to_vec()),
+            Err(Error::ChannelFull)
+        ));
+    }
+}
@@ -0,0 +1,270 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicUsize, Ordering},
+};
+
+use tokio_util::sync::CancellationToken;
+
+use crate::{PtyBackend, Result};
+
+/// Pastes are written in chunks of this size, so other input doesn't have to wait for the whole paste.
+pub const PASTE_CHUNK_LEN: usize = 4096;
+
+const BRACKETED_PASTE_START: &[u8] = b"\x1b[200~";
+const BRACKETED_PASTE_END: &[u8] = b"\x1b[201~";
+
+/// A large input, e.g. a pasted log file, which is written to a backend chunk by chunk.
+///
+/// Each chunk waits for the backend to accept it, so the paste never blocks and never fails because the input channel is full.
+#[derive(Debug)]
+pub struct Paste {
+    input: Vec<u8>,
+    handle: PasteHandle,
+    bracketed: bool,
+    finished: bool,
+}
+
+/// Tracks the progress of a [`Paste`] and cancels it.
+#[derive(Debug, Clone)]
+pub struct PasteHandle {
+    written: Arc<AtomicUsize>,
+    len: usize,
+    cancel: CancellationToken,
+}
+
+impl Paste {
+    /// A bracketed paste is recognized by its markers, end markers within the pasted text are removed.
+    pub fn new(input: Vec<u8>) -> Self {
+        let bracketed =
+            input.starts_with(BRACKETED_PASTE_START) && input.ends_with(BRACKETED_PASTE_END);
+        let input = if bracketed {
+            let text = &input[BRACKETED_PASTE_START.len()..input.len() - BRACKETED_PASTE_END.len()];
+            [
+                BRACKETED_PASTE_START,
+                &strip_end_markers(text.to_vec()),
+                BRACKETED_PASTE_END,
+            ]
+            .concat()
+        } else {
+            input
+        };
+
+        Self {
+            handle: PasteHandle {
+                written: Arc::default(),
+                len: input.len(),
+                cancel: CancellationToken::new(),
+            },
+            input,
+            bracketed,
+            finished: false,
+        }
+    }
+
+    pub fn handle(&self) -> PasteHandle {
+        self.handle.clone()
+    }
+
+    /// Writes the next chunk, returns `false` once the paste is complete or was cancelled.
+    ///
+    /// A cancelled bracketed paste is still terminated, so the application doesn't wait for the rest of it.
+    pub async fn write_next(&mut self, backend: &impl PtyBackend) -> Result<bool> {
+        if self.finished {
+            return Ok(false);
+        }
+
+        let written = self.handle.written();
+        if written >= self.input.len() {
+            self.finished = true;
+            return Ok(false);
+        }
+
+        if !self.handle.is_cancelled() {
+            let end = (written + PASTE_CHUNK_LEN).min(self.input.len());
+            let chunk = self.input[written..end].to_vec();
+
+            if let Some(result) = self
+                .handle
+                .cancel
+                .run_until_cancelled(backend.write(chunk))
+                .await
+            {
+                result?;
+                self.handle.written.store(end, Ordering::Relaxed);
+                return Ok(true);
+            }
+        }
+
+        self.finished = true;
+        if self.bracketed && written > 0 {
+            // the last chunk might have ended inside the end marker
+            let marker_start = self.input.len() - BRACKETED_PASTE_END.len();
+            let rest = if written > marker_start {
+                self.input[written..].to_vec()
+            } else {
+                BRACKETED_PASTE_END.to_vec()
+            };
+            backend.write(rest).await?;
+        }
+
+        Ok(false)
+    }
+
+    /// Writes the whole paste, stopping early if it is cancelled.
+    pub async fn write_to(mut self, backend: &impl PtyBackend) -> Result<()> {
+        while self.write_next(backend).await? {}
+        Ok(())
+    }
+}
+
+/// The shell would run everything after an end marker as if it was typed.
+fn strip_end_markers(mut text: Vec<u8>) -> Vec<u8> {
+    let mut start = 0;
+    while let Some(offset) = text[start..]
+        .windows(BRACKETED_PASTE_END.len())
+        .position(|window| window == BRACKETED_PASTE_END)
+    {
+        let position = start + offset;
+        text.drain(position..position + BRACKETED_PASTE_END.len());
+        // removing a marker can join the bytes around it into a new one
+        start = position.saturating_sub(BRACKETED_PASTE_END.len() - 1);
+    }
+
+    text
+}
+
+impl PasteHandle {
+    /// Stops the paste after the chunk which is currently written.
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    /// Returns how many bytes have been written so far.
+    pub fn written(&self) -> usize {
+        self.written.load(Ordering::Relaxed)
+    }
+
+    /// Returns the total size of the paste in bytes.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the progress between `0.0` and `1.0`.
+    pub fn progress(&self) -> f32 {
+        if self.len == 0 {
+            return 1.0;
+        }
+
+        self.written() as f32 / self.len as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{MemoryPty, MemoryPtyController, TerminalInput};
+
+    use super::*;
+
+    fn bracketed(text: &[u8]) -> Vec<u8> {
+        [BRACKETED_PASTE_START, text, BRACKETED_PASTE_END].concat()
+    }
+
+    fn written(controller: &mut MemoryPtyController) -> Vec<Vec<u8>> {
+        let mut chunks = Vec::new();
+        while let Some(input) = controller.try_recv() {
+            let TerminalInput::Input(chunk) = input else {
+                panic!("expected input");
+            };
+            chunks.push(chunk);
+        }
+
+        chunks
+    }
+
+    #[tokio::test]
+    async fn large_pastes_are_chunked() {
+        let (pty, _output, mut controller) = MemoryPty::new();
+        let input = vec![b'x'; 2 * PASTE_CHUNK_LEN + 100];
+
+        let paste = Paste::new(input.clone());
+        let handle = paste.handle();
+        paste.write_to(&pty).await.unwrap();
+
+        let chunks = written(&mut controller);
+        let lens: Vec<usize> = chunks.iter().map(Vec::len).collect();
+        assert_eq!(lens, [PASTE_CHUNK_LEN, PASTE_CHUNK_LEN, 100]);
+        assert_eq!(chunks.concat(), input);
+        assert_eq!(handle.progress(), 1.0);
+    }
+
+    #[tokio::test]
+    async fn cancelled_pastes_stop() {
+        let (pty, _output, mut controller) = MemoryPty::new();
+        let paste = Paste::new(vec![b'x'; 100 * PASTE_CHUNK_LEN]);
+        let handle = paste.handle();
+
+        // the paste waits for the full input channel
+        let task = tokio::spawn(async move { paste.write_to(&pty).await });
+        while handle.written() < 10 * PASTE_CHUNK_LEN {
+            tokio::task::yield_now().await;
+        }
+
+        handle.cancel();
+        task.await.unwrap().unwrap();
+
+        assert_eq!(written(&mut controller).len(), 10);
+        assert_eq!(handle.written(), 10 * PASTE_CHUNK_LEN);
+        assert!(controller.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn cancelled_bracketed_pastes_are_terminated() {
+        let (pty, _output, mut controller) = MemoryPty::new();
+        let input = bracketed(&vec![b'x'; 2 * PASTE_CHUNK_LEN]);
+
+        let mut paste = Paste::new(input.clone());
+        assert!(paste.write_next(&pty).await.unwrap());
+        paste.handle().cancel();
+        assert!(!paste.write_next(&pty).await.unwrap());
+        assert!(!paste.write_next(&pty).await.unwrap());
+
+        let chunks = written(&mut controller);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0], input[..PASTE_CHUNK_LEN]);
+        assert_eq!(chunks[1], BRACKETED_PASTE_END);
+
+        // nothing was pasted yet, so there is nothing to terminate
+        let mut paste = Paste::new(input);
+        paste.handle().cancel();
+        assert!(!paste.write_next(&pty).await.unwrap());
+        assert!(written(&mut controller).is_empty());
+    }
+
+    #[tokio::test]
+    async fn end_markers_in_bracketed_pastes_are_removed() {
+        let (pty, _output, mut controller) = MemoryPty::new();
+
+        let text = b"echo hi\x1b[201~rm -rf ~\r\x1b[20\x1b[201~1~";
+        let paste = Paste::new(bracketed(text));
+        assert_eq!(paste.handle().len(), bracketed(b"echo hirm -rf ~\r").len());
+        paste.write_to(&pty).await.unwrap();
+
+        assert_eq!(
+            written(&mut controller).concat(),
+            bracketed(b"echo hirm -rf ~\r")
+        );
+
+        // unbracketed input is written as it is
+        let paste = Paste::new(text.to_vec());
+        paste.write_to(&pty).await.unwrap();
+        assert_eq!(written(&mut controller).concat(), text);
+    }
+}
@@ -0,0 +1,161 @@
+use std::{
+    collections::VecDeque,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::mpsc;
+
+use crate::{Command, PtyProcess, Result};
+
+type Shell = (PtyProcess, mpsc::Receiver<Vec<u8>>);
+
+/// Keeps shells started in the background, so a new terminal shows a prompt right away.
+///
+/// The shells are started with the size and directory of the command, the terminal resizes them once it is attached.
+/// Their output is buffered until then.
+#[derive(Debug, Clone)]
+pub struct ShellPool {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    command: Command,
+    len: usize,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    idle: VecDeque<Shell>,
+    // shells which are currently being started
+    starting: usize,
+}
+
+impl ShellPool {
+    /// Keeps `len` shells ready, started with the given command.
+    ///
+    /// Nothing is started until [`ShellPool::fill`] or [`ShellPool::take`] is called.
+    pub fn new(command: Command, len: usize) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                command,
+                len,
+                state: Mutex::default(),
+            }),
+        }
+    }
+
+    /// Starts shells in the background until the pool is full.
+    ///
+    /// Has to be called from within a tokio runtime.
+    pub fn fill(&self) {
+        let missing = {
+            let mut state = self.inner.state.lock().unwrap();
+            let missing = self
+                .inner
+                .len
+                .saturating_sub(state.idle.len() + state.starting);
+            state.starting += missing;
+            missing
+        };
+
+        for _ in 0..missing {
+            let pool = self.clone();
+            tokio::spawn(async move {
+                let shell = PtyProcess::spawn(pool.inner.command.clone()).await;
+
+                let mut state = pool.inner.state.lock().unwrap();
+                state.starting -= 1;
+                // a failing shell is reported by the next `take`, which starts one itself
+                if let Ok(shell) = shell {
+                    state.idle.push_back(shell);
+                }
+            });
+        }
+    }
+
+    /// Hands out a waiting shell, or starts one if there is none, and refills the pool.
+    ///
+    /// `cwd` is the directory the shell should be in, `None` keeps the one of the command.
+    /// The waiting shells are only used if `cwd` is the directory they were started in,
+    /// otherwise a new shell is started there and the pool is left as it is.
+    pub async fn take(&self, cwd: Option<&Path>) -> Result<Shell> {
+        let command = self.inner.command.clone();
+        if let Some(cwd) = cwd
+            && command.get_cwd() != Some(cwd)
+        {
+            return PtyProcess::spawn(command.cwd(cwd)).await;
+        }
+
+        let idle = {
+            let mut state = self.inner.state.lock().unwrap();
+            // skip shells which exited while waiting
+            std::iter::from_fn(|| state.idle.pop_front())
+                .find(|(process, _)| process.try_exit_status().is_none())
+        };
+
+        let shell = match idle {
+            Some(shell) => shell,
+            None => PtyProcess::spawn(command).await?,
+        };
+
+        self.fill();
+
+        Ok(shell)
+    }
+
+    /// Returns the number of shells which are ready to be taken.
+    pub fn ready(&self) -> usize {
+        self.inner.state.lock().unwrap().idle.len()
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    async fn wait_for_output(output: &mut mpsc::Receiver<Vec<u8>>, expected: &str) {
+        let mut received = String::new();
+        while !received.contains(expected) {
+            let chunk = tokio::time::timeout(Duration::from_secs(10), output.recv())
+                .await
+                .expect("output didn't arrive")
+                .expect("output ended");
+            received.push_str(&String::from_utf8_lossy(&chunk));
+        }
+    }
+
+    #[tokio::test]
+    async fn waiting_shells_are_only_used_in_their_directory() {
+        let pool_dir = std::env::temp_dir();
+        let pool = ShellPool::new(Command::new("sh").env("PS1", "$ ").cwd(&pool_dir), 1);
+        pool.fill();
+        while pool.ready() == 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let dir = std::env::temp_dir().join(format!("async-pty-pool-it's-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // another directory gets a new shell
+        let (process, mut output) = pool.take(Some(&dir)).await.unwrap();
+        process.write(b"pwd\r".to_vec()).await.unwrap();
+        wait_for_output(&mut output, &format!("{}\r\n", dir.display())).await;
+        assert_eq!(pool.ready(), 1);
+
+        let (process, mut output) = pool.take(Some(&pool_dir)).await.unwrap();
+        process.write(b"pwd\r".to_vec()).await.unwrap();
+        wait_for_output(&mut output, &format!("{}\r\n", pool_dir.display())).await;
+
+        // a shell is started in the background to replace the waiting one
+        while pool.ready() == 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        std::fs::remove_dir(&dir).unwrap();
+    }
+}
@@ -0,0 +1,50 @@
+use std::path::PathBuf;
+
+/// Information about a process running inside a pty.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    /// The executable name as reported by the kernel, e.g. `htop`.
+    pub name: String,
+    pub argv: Vec<String>,
+    /// The current working directory of the process, if it can be read.
+    pub cwd: Option<PathBuf>,
+}
+
+impl ProcessInfo {
+    /// Reads the information from `/proc/<pid>`.
+    #[cfg(target_os = "linux")]
+    pub fn from_pid(pid: u32) -> Option<Self> {
+        let proc_dir = PathBuf::from(format!("/proc/{pid}"));
+
+        let name = std::fs::read_to_string(proc_dir.join("comm"))
+            .ok()?
+            .trim_end()
+            .to_string();
+
+        let argv = std::fs::read(proc_dir.join("cmdline"))
+            .map(|cmdline| {
+                cmdline
+                    .split(|byte| *byte == 0)
+                    .filter(|arg| !arg.is_empty())
+                    .map(|arg| String::from_utf8_lossy(arg).into_owned())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let cwd = std::fs::read_link(proc_dir.join("cwd")).ok();
+
+        Some(Self {
+            pid,
+            name,
+            argv,
+            cwd,
+        })
+    }
+
+    /// Process information is currently only available on linux.
+    #[cfg(not(target_os = "linux"))]
+    pub fn from_pid(_pid: u32) -> Option<Self> {
+        None
+    }
+}
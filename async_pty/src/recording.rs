@@ -0,0 +1,356 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::TerminalSize;
+
+/// Records a terminal session as an [asciicast v2](https://docs.asciinema.org/manual/asciicast/v2/) file.
+///
+/// Events are buffered, so only a recording which was [finished](Recorder::finish) is guaranteed to be complete.
+/// Use [`Recorder::flush`] to write the buffered events earlier.
+pub struct Recorder<W: Write = BufWriter<File>> {
+    writer: W,
+    start: Instant,
+    output: Utf8Decoder,
+    input: Utf8Decoder,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    version: u8,
+    width: u16,
+    height: u16,
+    #[serde(default)]
+    timestamp: u64,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    env: BTreeMap<String, String>,
+}
+
+/// A parsed asciicast v2 recording, e.g. one written by [`Recorder`].
+#[derive(Debug, Clone)]
+pub struct Cast {
+    /// The size of the terminal when the recording started.
+    pub size: TerminalSize,
+    pub events: Vec<CastEvent>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CastEvent {
+    /// Time since the start of the recording.
+    pub time: Duration,
+    pub kind: CastEventKind,
+}
+
+#[derive(Debug, Clone)]
+pub enum CastEventKind {
+    Output(String),
+    Input(String),
+    Resize(TerminalSize),
+    Marker(String),
+}
+
+impl Cast {
+    /// Parses the content of a `.cast` file. Unknown event types are skipped.
+    pub fn parse(content: &str) -> io::Result<Self> {
+        let mut lines = content.lines().filter(|line| !line.trim().is_empty());
+
+        let header = lines.next().ok_or_else(|| invalid_data("missing header"))?;
+        let header: Header = serde_json::from_str(header)?;
+        if header.version != 2 {
+            return Err(invalid_data(format!(
+                "unsupported asciicast version {}",
+                header.version
+            )));
+        }
+
+        let mut events = Vec::new();
+        for line in lines {
+            let (time, kind, data): (f64, String, String) = serde_json::from_str(line)?;
+            let time = Duration::try_from_secs_f64(time).map_err(invalid_data)?;
+
+            let kind = match kind.as_str() {
+                "o" => CastEventKind::Output(data),
+                "i" => CastEventKind::Input(data),
+                "r" => CastEventKind::Resize(parse_size(&data)?),
+                "m" => CastEventKind::Marker(data),
+                _ => continue,
+            };
+
+            events.push(CastEvent { time, kind });
+        }
+
+        Ok(Self {
+            size: TerminalSize {
+                cols: header.width,
+                rows: header.height,
+                ..Default::default()
+            },
+            events,
+        })
+    }
+
+    /// The time of the last event.
+    pub fn duration(&self) -> Duration {
+        self.events
+            .last()
+            .map(|event| event.time)
+            .unwrap_or_default()
+    }
+}
+
+fn parse_size(data: &str) -> io::Result<TerminalSize> {
+    let (cols, rows) = data
+        .split_once('x')
+        .ok_or_else(|| invalid_data(format!("invalid resize event {data:?}")))?;
+
+    Ok(TerminalSize {
+        cols: cols.parse().map_err(invalid_data)?,
+        rows: rows.parse().map_err(invalid_data)?,
+        ..Default::default()
+    })
+}
+
+fn invalid_data(err: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+impl Recorder {
+    /// Creates the file at `path`, replacing an existing one.
+    pub fn create(
+        path: impl AsRef<Path>,
+        size: &TerminalSize,
+        env: impl IntoIterator<Item = (String, String)>,
+    ) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?), size, env)
+    }
+}
+
+impl<W: Write> Recorder<W> {
+    /// Writes the header and starts the clock for all following events.
+    ///
+    /// `env` describes the recorded process, usually its `SHELL` and `TERM`,
+    /// see [`PtyProcess::recording_env`](crate::PtyProcess::recording_env).
+    pub fn new(
+        mut writer: W,
+        size: &TerminalSize,
+        env: impl IntoIterator<Item = (String, String)>,
+    ) -> io::Result<Self> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+
+        let header = Header {
+            version: 2,
+            width: size.cols,
+            height: size.rows,
+            timestamp,
+            env: env.into_iter().collect(),
+        };
+
+        let mut line = serde_json::to_vec(&header)?;
+        line.push(b'\n');
+        writer.write_all(&line)?;
+
+        Ok(Self {
+            writer,
+            start: Instant::now(),
+            output: Utf8Decoder::default(),
+            input: Utf8Decoder::default(),
+        })
+    }
+
+    /// Records bytes written by the process.
+    pub fn output(&mut self, output: &[u8]) -> io::Result<()> {
+        let text = self.output.decode(output);
+        self.event("o", &text)
+    }
+
+    /// Records bytes sent to the process.
+    pub fn input(&mut self, input: &[u8]) -> io::Result<()> {
+        let text = self.input.decode(input);
+        self.event("i", &text)
+    }
+
+    pub fn resize(&mut self, size: &TerminalSize) -> io::Result<()> {
+        self.event("r", &format!("{}x{}", size.cols, size.rows))
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Flushes and returns the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn event(&mut self, kind: &str, data: &str) -> io::Result<()> {
+        // chunks ending in the middle of a character are held back until the rest arrives
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        let time = self.start.elapsed().as_secs_f64();
+        let mut line = serde_json::to_vec(&(time, kind, data))?;
+        line.push(b'\n');
+        self.writer.write_all(&line)
+    }
+}
+
+impl<W: Write> std::fmt::Debug for Recorder<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Recorder")
+            .field("start", &self.start)
+            .finish()
+    }
+}
+
+/// The pty delivers bytes in arbitrary chunks, but asciicast events have to be valid UTF-8.
+#[derive(Default)]
+struct Utf8Decoder {
+    pending: Vec<u8>,
+}
+
+impl Utf8Decoder {
+    fn decode(&mut self, bytes: &[u8]) -> String {
+        self.pending.extend_from_slice(bytes);
+
+        let mut text = String::new();
+        let mut rest = self.pending.as_slice();
+        while !rest.is_empty() {
+            match std::str::from_utf8(rest) {
+                Ok(valid) => {
+                    text.push_str(valid);
+                    rest = &[];
+                }
+                Err(err) => {
+                    let (valid, invalid) = rest.split_at(err.valid_up_to());
+                    text.push_str(&String::from_utf8_lossy(valid));
+
+                    match err.error_len() {
+                        Some(len) => {
+                            text.push(char::REPLACEMENT_CHARACTER);
+                            rest = &invalid[len..];
+                        }
+                        // incomplete character at the end
+                        None => {
+                            rest = invalid;
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        let consumed = self.pending.len() - rest.len();
+        self.pending.drain(..consumed);
+
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn size(cols: u16, rows: u16) -> TerminalSize {
+        TerminalSize {
+            cols,
+            rows,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn record_and_parse() {
+        let env = [
+            ("SHELL".to_string(), "/bin/zsh".to_string()),
+            ("TERM".to_string(), "xterm-256color".to_string()),
+        ];
+        let mut recorder = Recorder::new(Vec::new(), &size(80, 24), env).unwrap();
+
+        // "é" split between two reads
+        recorder.output(b"caf\xc3").unwrap();
+        recorder.output(b"\xa9\r\n").unwrap();
+        recorder.input(b"ls\r").unwrap();
+        recorder.resize(&size(120, 40)).unwrap();
+        let content = String::from_utf8(recorder.finish().unwrap()).unwrap();
+
+        let header: Header = serde_json::from_str(content.lines().next().unwrap()).unwrap();
+        assert_eq!(header.env["SHELL"], "/bin/zsh");
+        assert_eq!(header.env["TERM"], "xterm-256color");
+
+        let cast = Cast::parse(&content).unwrap();
+        assert_eq!((cast.size.cols, cast.size.rows), (80, 24));
+
+        let events: Vec<_> = cast.events.iter().map(|event| &event.kind).collect();
+        let [
+            CastEventKind::Output(first),
+            CastEventKind::Output(second),
+            CastEventKind::Input(input),
+            CastEventKind::Resize(resized),
+        ] = events.as_slice()
+        else {
+            panic!("unexpected events {events:?}");
+        };
+        assert_eq!(first, "caf");
+        assert_eq!(second, "é\r\n");
+        assert_eq!(input, "ls\r");
+        assert_eq!((resized.cols, resized.rows), (120, 40));
+
+        assert!(cast.events.is_sorted_by_key(|event| event.time));
+        assert_eq!(cast.duration(), cast.events[3].time);
+    }
+
+    #[test]
+    fn parse_skips_unknown_events() {
+        let content = concat!(
+            "{\"version\": 2, \"width\": 100, \"height\": 30}\n",
+            "[0.5, \"o\", \"hello\"]\n",
+            "\n",
+            "[1.0, \"x\", \"unknown\"]\n",
+            "[1.5, \"m\", \"chapter\"]\n",
+        );
+
+        let cast = Cast::parse(content).unwrap();
+        assert_eq!((cast.size.cols, cast.size.rows), (100, 30));
+        assert_eq!(cast.events.len(), 2);
+        assert!(
+            matches!(&cast.events[1].kind, CastEventKind::Marker(marker) if marker == "chapter")
+        );
+        assert_eq!(cast.duration(), Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn parse_rejects_invalid_casts() {
+        assert!(Cast::parse("").is_err());
+        assert!(Cast::parse("{\"version\": 1, \"width\": 80, \"height\": 24}").is_err());
+        assert!(
+            Cast::parse("{\"version\": 2, \"width\": 80, \"height\": 24}\n[0.1, \"r\", \"80\"]")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn utf8_across_chunks() {
+        let mut decoder = Utf8Decoder::default();
+
+        // a four byte character in three chunks
+        let emoji = "🦀".as_bytes();
+        assert_eq!(decoder.decode(&emoji[..1]), "");
+        assert_eq!(decoder.decode(&emoji[1..3]), "");
+        assert_eq!(decoder.decode(&[&emoji[3..], b"!"].concat()), "🦀!");
+
+        // invalid bytes don't hold back the rest
+        assert_eq!(decoder.decode(b"a\xffb"), "a\u{fffd}b");
+        assert_eq!(decoder.decode(b"\xc3("), "\u{fffd}(");
+    }
+}
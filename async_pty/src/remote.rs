@@ -0,0 +1,652 @@
+use std::{
+    fmt::Display,
+    io,
+    pin::Pin,
+    str::FromStr,
+    task::{Context, Poll},
+};
+
+#[cfg(unix)]
+use std::path::PathBuf;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+
+use crate::{ExitStatus, TerminalSize};
+
+/// Frames larger than this are rejected to protect against corrupted streams.
+pub const MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+
+// tag + payload length
+const HEADER_LEN: usize = 1 + 4;
+
+const TAG_OUTPUT: u8 = 0;
+const TAG_INPUT: u8 = 1;
+const TAG_RESIZE: u8 = 2;
+const TAG_EXIT: u8 = 3;
+const TAG_LIST: u8 = 4;
+const TAG_SESSIONS: u8 = 5;
+const TAG_ATTACH: u8 = 6;
+const TAG_ATTACHED: u8 = 7;
+const TAG_CLOSE: u8 = 8;
+const TAG_KILL: u8 = 9;
+const TAG_AUTH: u8 = 10;
+
+const EXIT_CODE: u8 = 0;
+const EXIT_SIGNAL: u8 = 1;
+const EXIT_UNKNOWN: u8 = 2;
+
+/// A message of the remote pty protocol.
+///
+/// Every frame starts with a one byte tag and the length of the payload as big endian `u32`.
+///
+/// A client starts a new session by sending input or its size.
+/// Before that it may send [`Frame::List`] any number of times, or [`Frame::Attach`] to join an existing session instead.
+/// A client which only wants to end a session sends [`Frame::Kill`].
+/// Over TCP the client has to send [`Frame::Auth`] first.
+#[derive(Debug, Clone)]
+pub enum Frame {
+    /// Output of the process, sent by the server.
+    Output(Vec<u8>),
+    /// Input for the process, sent by the client.
+    Input(Vec<u8>),
+    /// The client was resized.
+    Resize(TerminalSize),
+    /// The process has exited, this is the last frame sent by the server.
+    /// Contains `None` if the exit status could not be determined.
+    Exit(Option<ExitStatus>),
+    /// Asks the server for its sessions, sent by the client.
+    List,
+    /// The sessions running on the server, sent in reply to [`Frame::List`].
+    Sessions(Vec<SessionInfo>),
+    /// Attaches to the session with the given id, sent by the client.
+    ///
+    /// The server replies with [`Frame::Exit`] if there is no such session.
+    Attach(u32),
+    /// The id of the session the client is connected to, sent by the server before any output.
+    Attached(u32),
+    /// Ends the session once the client is gone instead of keeping it for later, sent by the client.
+    Close,
+    /// Hangs up the session with the given id without attaching to it, sent by the client.
+    ///
+    /// The server closes the connection afterwards.
+    Kill(u32),
+    /// The token of a server listening on TCP, sent by the client before any other frame.
+    ///
+    /// The server closes the connection if the token is wrong.
+    Auth(Vec<u8>),
+}
+
+/// A session of a server which keeps its shells running between connections.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionInfo {
+    pub id: u32,
+    /// The name of the process in the foreground of the session, e.g. `htop`.
+    pub name: String,
+}
+
+impl Frame {
+    /// Appends the encoded frame to `buffer`.
+    pub fn encode(&self, buffer: &mut Vec<u8>) {
+        let (tag, payload) = match self {
+            Self::Output(output) => (TAG_OUTPUT, output.clone()),
+            Self::Input(input) => (TAG_INPUT, input.clone()),
+            Self::Resize(size) => (
+                TAG_RESIZE,
+                [size.cols, size.rows, size.pixel_width, size.pixel_height]
+                    .iter()
+                    .flat_map(|value| value.to_be_bytes())
+                    .collect(),
+            ),
+            Self::Exit(status) => (TAG_EXIT, encode_exit(status.as_ref())),
+            Self::List => (TAG_LIST, Vec::new()),
+            Self::Sessions(sessions) => (TAG_SESSIONS, encode_sessions(sessions)),
+            Self::Attach(id) => (TAG_ATTACH, id.to_be_bytes().to_vec()),
+            Self::Attached(id) => (TAG_ATTACHED, id.to_be_bytes().to_vec()),
+            Self::Close => (TAG_CLOSE, Vec::new()),
+            Self::Kill(id) => (TAG_KILL, id.to_be_bytes().to_vec()),
+            Self::Auth(token) => (TAG_AUTH, token.clone()),
+        };
+
+        buffer.push(tag);
+        buffer.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        buffer.extend_from_slice(&payload);
+    }
+
+    /// Removes the first frame from `buffer` and returns it.
+    ///
+    /// Returns `None` if the buffer doesn't contain a complete frame yet.
+    pub fn decode(buffer: &mut Vec<u8>) -> io::Result<Option<Self>> {
+        let Some(len) = frame_len(buffer)? else {
+            return Ok(None);
+        };
+
+        if buffer.len() < HEADER_LEN + len {
+            return Ok(None);
+        }
+
+        let tag = buffer[0];
+        let payload: Vec<u8> = buffer.drain(..HEADER_LEN + len).skip(HEADER_LEN).collect();
+
+        Self::from_parts(tag, payload).map(Some)
+    }
+
+    fn from_parts(tag: u8, payload: Vec<u8>) -> io::Result<Self> {
+        match tag {
+            TAG_OUTPUT => Ok(Self::Output(payload)),
+            TAG_INPUT => Ok(Self::Input(payload)),
+            TAG_RESIZE => {
+                let values: Vec<u16> = payload
+                    .chunks_exact(2)
+                    .map(|value| u16::from_be_bytes([value[0], value[1]]))
+                    .collect();
+
+                match values[..] {
+                    [cols, rows, pixel_width, pixel_height] => Ok(Self::Resize(TerminalSize {
+                        cols,
+                        rows,
+                        pixel_width,
+                        pixel_height,
+                    })),
+                    _ => Err(invalid_data("invalid resize frame")),
+                }
+            }
+            TAG_EXIT => decode_exit(&payload).map(Self::Exit),
+            TAG_LIST => Ok(Self::List),
+            TAG_SESSIONS => decode_sessions(&payload).map(Self::Sessions),
+            TAG_ATTACH => decode_id(&payload).map(Self::Attach),
+            TAG_ATTACHED => decode_id(&payload).map(Self::Attached),
+            TAG_CLOSE => Ok(Self::Close),
+            TAG_KILL => decode_id(&payload).map(Self::Kill),
+            TAG_AUTH => Ok(Self::Auth(payload)),
+            tag => Err(invalid_data(format!("unknown frame tag {tag}"))),
+        }
+    }
+}
+
+/// Reads the next frame, returns `None` if the stream ended between frames.
+pub async fn read_frame(reader: &mut (impl AsyncRead + Unpin)) -> io::Result<Option<Frame>> {
+    let mut header = [0u8; HEADER_LEN];
+    match reader.read_exact(&mut header).await {
+        Ok(_) => {}
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err),
+    }
+
+    let len = frame_len(&header)?.unwrap_or_default();
+    let mut payload = vec![0u8; len];
+    reader.read_exact(&mut payload).await?;
+
+    Frame::from_parts(header[0], payload).map(Some)
+}
+
+pub async fn write_frame(writer: &mut (impl AsyncWrite + Unpin), frame: &Frame) -> io::Result<()> {
+    let mut buffer = Vec::new();
+    frame.encode(&mut buffer);
+    writer.write_all(&buffer).await?;
+    writer.flush().await
+}
+
+/// Asks the server at `address` for its sessions.
+pub async fn list_sessions(address: &Address) -> io::Result<Vec<SessionInfo>> {
+    let mut connection = Connection::connect(address).await?;
+    write_frame(&mut connection, &Frame::List).await?;
+
+    loop {
+        match read_frame(&mut connection).await? {
+            Some(Frame::Sessions(sessions)) => return Ok(sessions),
+            Some(_) => {}
+            None => return Err(io::ErrorKind::UnexpectedEof.into()),
+        }
+    }
+}
+
+/// Ends a session on the server at `address`, which hangs up its shell.
+pub async fn close_session(address: &Address, id: u32) -> io::Result<()> {
+    let mut connection = Connection::connect(address).await?;
+    write_frame(&mut connection, &Frame::Kill(id)).await?;
+
+    // the server hangs up once the session is closed
+    while read_frame(&mut connection).await?.is_some() {}
+    Ok(())
+}
+
+fn frame_len(buffer: &[u8]) -> io::Result<Option<usize>> {
+    let Some(len) = buffer.get(1..HEADER_LEN) else {
+        return Ok(None);
+    };
+
+    let len = u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(invalid_data(format!("frame of {len} bytes is too large")));
+    }
+
+    Ok(Some(len))
+}
+
+fn encode_exit(status: Option<&ExitStatus>) -> Vec<u8> {
+    match status {
+        Some(status) => match status.signal() {
+            Some(signal) => [&[EXIT_SIGNAL][..], signal.as_bytes()].concat(),
+            None => [&[EXIT_CODE][..], &status.exit_code().to_be_bytes()].concat(),
+        },
+        None => vec![EXIT_UNKNOWN],
+    }
+}
+
+fn decode_exit(payload: &[u8]) -> io::Result<Option<ExitStatus>> {
+    match payload {
+        [EXIT_CODE, a, b, c, d] => Ok(Some(ExitStatus::with_exit_code(u32::from_be_bytes([
+            *a, *b, *c, *d,
+        ])))),
+        [EXIT_SIGNAL, signal @ ..] => Ok(Some(ExitStatus::with_signal(&String::from_utf8_lossy(
+            signal,
+        )))),
+        [EXIT_UNKNOWN] => Ok(None),
+        _ => Err(invalid_data("invalid exit frame")),
+    }
+}
+
+fn encode_sessions(sessions: &[SessionInfo]) -> Vec<u8> {
+    let mut payload = Vec::new();
+    for session in sessions {
+        // longer names are cut off, they are only shown to the user
+        let name = &session.name.as_bytes()[..session.name.len().min(u16::MAX as usize)];
+
+        payload.extend_from_slice(&session.id.to_be_bytes());
+        payload.extend_from_slice(&(name.len() as u16).to_be_bytes());
+        payload.extend_from_slice(name);
+    }
+
+    payload
+}
+
+fn decode_sessions(mut payload: &[u8]) -> io::Result<Vec<SessionInfo>> {
+    let mut sessions = Vec::new();
+    while !payload.is_empty() {
+        let [a, b, c, d, len_a, len_b, rest @ ..] = payload else {
+            return Err(invalid_data("invalid sessions frame"));
+        };
+
+        let len = u16::from_be_bytes([*len_a, *len_b]) as usize;
+        let Some(name) = rest.get(..len) else {
+            return Err(invalid_data("invalid sessions frame"));
+        };
+
+        sessions.push(SessionInfo {
+            id: u32::from_be_bytes([*a, *b, *c, *d]),
+            name: String::from_utf8_lossy(name).into_owned(),
+        });
+        payload = &rest[len..];
+    }
+
+    Ok(sessions)
+}
+
+fn decode_id(payload: &[u8]) -> io::Result<u32> {
+    match payload {
+        [a, b, c, d] => Ok(u32::from_be_bytes([*a, *b, *c, *d])),
+        _ => Err(invalid_data("invalid session id")),
+    }
+}
+
+fn invalid_data(err: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+/// Where a remote pty server listens, written as `tcp:<token>@<host>:<port>` or `unix:<path>`.
+///
+/// The token is sent to the server when connecting, it is left out when the address is displayed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Address {
+    Tcp {
+        address: String,
+        token: Option<String>,
+    },
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl FromStr for Address {
+    type Err = io::Error;
+
+    fn from_str(address: &str) -> Result<Self, Self::Err> {
+        if let Some(address) = address.strip_prefix("tcp:") {
+            return Ok(match address.rsplit_once('@') {
+                Some((token, address)) => Self::Tcp {
+                    address: address.to_string(),
+                    token: Some(token.to_string()),
+                },
+                None => Self::Tcp {
+                    address: address.to_string(),
+                    token: None,
+                },
+            });
+        }
+
+        #[cfg(unix)]
+        if let Some(path) = address.strip_prefix("unix:") {
+            return Ok(Self::Unix(path.into()));
+        }
+
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "invalid address {address:?}, expected tcp:<token>@<host>:<port> or unix:<path>"
+            ),
+        ))
+    }
+}
+
+impl Display for Address {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Tcp { address, .. } => write!(f, "tcp:{address}"),
+            #[cfg(unix)]
+            Self::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// A connection between a remote pty client and server.
+#[derive(Debug)]
+pub enum Connection {
+    Tcp(tokio::net::TcpStream),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixStream),
+}
+
+impl Connection {
+    pub async fn connect(address: &Address) -> io::Result<Self> {
+        match address {
+            Address::Tcp { address, token } => {
+                let stream = tokio::net::TcpStream::connect(address).await?;
+                // input is mostly single key presses
+                stream.set_nodelay(true)?;
+
+                let mut connection = Self::Tcp(stream);
+                let token = token.as_deref().unwrap_or_default().as_bytes().to_vec();
+                write_frame(&mut connection, &Frame::Auth(token)).await?;
+                Ok(connection)
+            }
+            #[cfg(unix)]
+            Address::Unix(path) => Ok(Self::Unix(tokio::net::UnixStream::connect(path).await?)),
+        }
+    }
+}
+
+impl AsyncRead for Connection {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+            #[cfg(unix)]
+            Self::Unix(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for Connection {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+            #[cfg(unix)]
+            Self::Unix(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+            #[cfg(unix)]
+            Self::Unix(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+            #[cfg(unix)]
+            Self::Unix(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
+/// Accepts connections of remote pty clients.
+#[derive(Debug)]
+pub enum Listener {
+    Tcp(tokio::net::TcpListener),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener),
+}
+
+impl Listener {
+    /// A unix socket left behind by a server which is no longer running is replaced.
+    /// A new unix socket can only be used by its owner.
+    pub async fn bind(address: &Address) -> io::Result<Self> {
+        match address {
+            Address::Tcp { address, .. } => {
+                Ok(Self::Tcp(tokio::net::TcpListener::bind(address).await?))
+            }
+            #[cfg(unix)]
+            Address::Unix(path) => {
+                use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+                let is_socket = std::fs::symlink_metadata(path)
+                    .is_ok_and(|metadata| metadata.file_type().is_socket());
+                if is_socket && tokio::net::UnixStream::connect(path).await.is_err() {
+                    std::fs::remove_file(path)?;
+                }
+
+                let listener = tokio::net::UnixListener::bind(path)?;
+                std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+                Ok(Self::Unix(listener))
+            }
+        }
+    }
+
+    /// Connections of other users to a unix socket are refused.
+    ///
+    /// Clients connecting over TCP still have to be checked for their [`Frame::Auth`].
+    pub async fn accept(&self) -> io::Result<Connection> {
+        match self {
+            Self::Tcp(listener) => {
+                let (stream, _) = listener.accept().await?;
+                stream.set_nodelay(true)?;
+                Ok(Connection::Tcp(stream))
+            }
+            #[cfg(unix)]
+            Self::Unix(listener) => {
+                let (stream, _) = listener.accept().await?;
+
+                // the permissions of the socket only apply once they were set after binding it
+                let uid = stream.peer_cred()?.uid();
+                if uid != unsafe { libc::geteuid() } {
+                    return Err(io::Error::new(
+                        io::ErrorKind::PermissionDenied,
+                        format!("refused connection of user {uid}"),
+                    ));
+                }
+
+                Ok(Connection::Unix(stream))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(frame: Frame) {
+        let mut buffer = Vec::new();
+        frame.encode(&mut buffer);
+        // a second frame must stay in the buffer
+        Frame::List.encode(&mut buffer);
+
+        let decoded = Frame::decode(&mut buffer).unwrap().unwrap();
+        // frames can't be compared, as the exit status doesn't implement `PartialEq`
+        assert_eq!(format!("{decoded:?}"), format!("{frame:?}"));
+        assert!(matches!(Frame::decode(&mut buffer), Ok(Some(Frame::List))));
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn frames_round_trip() {
+        round_trip(Frame::Output(b"output".to_vec()));
+        round_trip(Frame::Input(vec![0, 255, 27]));
+        round_trip(Frame::Resize(TerminalSize {
+            cols: 120,
+            rows: 40,
+            pixel_width: 960,
+            pixel_height: 640,
+        }));
+        round_trip(Frame::Exit(Some(ExitStatus::with_exit_code(1))));
+        round_trip(Frame::Exit(Some(ExitStatus::with_signal("Killed"))));
+        round_trip(Frame::Exit(None));
+        round_trip(Frame::List);
+        round_trip(Frame::Sessions(vec![
+            SessionInfo {
+                id: 1,
+                name: "htop".to_string(),
+            },
+            SessionInfo {
+                id: u32::MAX,
+                name: String::new(),
+            },
+        ]));
+        round_trip(Frame::Attach(42));
+        round_trip(Frame::Attached(42));
+        round_trip(Frame::Close);
+        round_trip(Frame::Kill(42));
+        round_trip(Frame::Auth(b"secret".to_vec()));
+    }
+
+    #[test]
+    fn incomplete_frames() {
+        let mut buffer = Vec::new();
+        Frame::Output(b"output".to_vec()).encode(&mut buffer);
+        let complete = buffer.clone();
+
+        for len in 0..complete.len() {
+            let mut partial = complete[..len].to_vec();
+            assert!(matches!(Frame::decode(&mut partial), Ok(None)));
+            assert_eq!(partial.len(), len);
+        }
+    }
+
+    #[test]
+    fn oversized_frames_are_rejected() {
+        let mut buffer = vec![TAG_OUTPUT];
+        buffer.extend_from_slice(&(MAX_FRAME_LEN as u32 + 1).to_be_bytes());
+
+        let err = Frame::decode(&mut buffer).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn invalid_frames_are_rejected() {
+        for (tag, payload) in [
+            (TAG_RESIZE, &[0, 80, 0, 24][..]),
+            (TAG_EXIT, &[EXIT_CODE, 1][..]),
+            (TAG_SESSIONS, &[0, 0, 0, 1, 0, 5, b'a'][..]),
+            (TAG_ATTACH, &[1, 2][..]),
+            (42, &[][..]),
+        ] {
+            let mut buffer = vec![tag];
+            buffer.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+            buffer.extend_from_slice(payload);
+
+            let err = Frame::decode(&mut buffer).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn addresses() {
+        let address: Address = "tcp:secret@127.0.0.1:7777".parse().unwrap();
+        assert_eq!(
+            address,
+            Address::Tcp {
+                address: "127.0.0.1:7777".to_string(),
+                token: Some("secret".to_string()),
+            }
+        );
+        assert_eq!(address.to_string(), "tcp:127.0.0.1:7777");
+
+        let address: Address = "tcp:[::1]:7777".parse().unwrap();
+        assert_eq!(
+            address,
+            Address::Tcp {
+                address: "[::1]:7777".to_string(),
+                token: None,
+            }
+        );
+
+        assert!("127.0.0.1:7777".parse::<Address>().is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn unix_sockets_are_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!("remote-private-{}.sock", std::process::id()));
+        let address = Address::Unix(path.clone());
+        let listener = Listener::bind(&address).await.unwrap();
+
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // the owner can still connect
+        let (client, server) = tokio::join!(Connection::connect(&address), listener.accept());
+        client.unwrap();
+        server.unwrap();
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn read_and_write_frames() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+
+        let writer = tokio::spawn(async move {
+            write_frame(&mut client, &Frame::Input(vec![b'x'; 1000]))
+                .await
+                .unwrap();
+            write_frame(&mut client, &Frame::Close).await.unwrap();
+        });
+
+        let Some(Frame::Input(input)) = read_frame(&mut server).await.unwrap() else {
+            panic!("expected input");
+        };
+        assert_eq!(input, vec![b'x'; 1000]);
+        assert!(matches!(
+            read_frame(&mut server).await.unwrap(),
+            Some(Frame::Close)
+        ));
+
+        writer.await.unwrap();
+        // the stream ended between frames
+        assert!(read_frame(&mut server).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn truncated_stream() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+
+        let mut buffer = Vec::new();
+        Frame::Output(b"output".to_vec()).encode(&mut buffer);
+        client.write_all(&buffer[..buffer.len() - 1]).await.unwrap();
+        drop(client);
+
+        let err = read_frame(&mut server).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
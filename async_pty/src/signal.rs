@@ -0,0 +1,41 @@
+/// Signals which can be sent to the processes running inside a pty.
+///
+/// Signals are only supported on unix systems.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    /// `SIGHUP`
+    Hangup,
+    /// `SIGINT`, the equivalent of pressing Ctrl+C
+    Interrupt,
+    /// `SIGQUIT`, the equivalent of pressing Ctrl+\
+    Quit,
+    /// `SIGKILL`
+    Kill,
+    /// `SIGTERM`
+    Terminate,
+    /// `SIGTSTP`, the equivalent of pressing Ctrl+Z
+    Suspend,
+    /// `SIGCONT`
+    Continue,
+    /// `SIGUSR1`
+    User1,
+    /// `SIGUSR2`
+    User2,
+}
+
+#[cfg(unix)]
+impl Signal {
+    pub(crate) fn as_raw(self) -> libc::c_int {
+        match self {
+            Self::Hangup => libc::SIGHUP,
+            Self::Interrupt => libc::SIGINT,
+            Self::Quit => libc::SIGQUIT,
+            Self::Kill => libc::SIGKILL,
+            Self::Terminate => libc::SIGTERM,
+            Self::Suspend => libc::SIGTSTP,
+            Self::Continue => libc::SIGCONT,
+            Self::User1 => libc::SIGUSR1,
+            Self::User2 => libc::SIGUSR2,
+        }
+    }
+}
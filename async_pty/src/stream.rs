@@ -0,0 +1,355 @@
+use std::{
+    io,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, ready},
+};
+
+use futures_core::Stream;
+use futures_sink::Sink;
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    sync::mpsc,
+};
+use tokio_util::sync::{PollSender, ReusableBoxFuture};
+
+use crate::{Error, PtyProcess, Result, TerminalInput, TerminalSize};
+
+/// A [`PtyProcess`] together with its output, usable as a byte stream.
+///
+/// Implements tokio's [`AsyncRead`] and [`AsyncWrite`], so it works with `tokio::io::copy` and codecs,
+/// as well as a [`Stream`] of output chunks and a [`Sink`] of input chunks.
+/// The stream ends once the process closed the pty, resizing goes through a [`ResizeHandle`].
+/// Flushing waits until all input was handed to the io task, which writes it in order.
+/// Shutting down only stops writing through the stream, the pty stays open until the stream is dropped.
+///
+/// Dropping the stream drops the process, which hangs up the pty.
+#[derive(Debug)]
+pub struct PtyStream {
+    process: PtyProcess,
+    output: mpsc::Receiver<Vec<u8>>,
+    // output which didn't fit into the read buffer
+    pending: Vec<u8>,
+    input: PollSender<TerminalInput>,
+    // resolves once all queued input was taken, `false` if the io task is gone
+    flush: Option<ReusableBoxFuture<'static, bool>>,
+}
+
+/// Resizes the pty of a [`PtyProcess`] without access to the process itself.
+#[derive(Debug, Clone)]
+pub struct ResizeHandle {
+    write: mpsc::Sender<TerminalInput>,
+    failure: Arc<Mutex<Option<Error>>>,
+}
+
+impl PtyProcess {
+    /// Combines the process with its output, see [`PtyStream`].
+    pub fn into_stream(self, output: mpsc::Receiver<Vec<u8>>) -> PtyStream {
+        PtyStream::new(self, output)
+    }
+
+    pub fn resize_handle(&self) -> ResizeHandle {
+        ResizeHandle {
+            write: self.write.clone(),
+            failure: self.failure.clone(),
+        }
+    }
+}
+
+impl PtyStream {
+    pub fn new(process: PtyProcess, output: mpsc::Receiver<Vec<u8>>) -> Self {
+        let input = PollSender::new(process.write.clone());
+
+        Self {
+            process,
+            output,
+            pending: Vec::new(),
+            input,
+            flush: None,
+        }
+    }
+
+    /// Gives access to the process, e.g. to send signals or wait for it to exit.
+    pub fn process(&self) -> &PtyProcess {
+        &self.process
+    }
+
+    pub fn resize_handle(&self) -> ResizeHandle {
+        self.process.resize_handle()
+    }
+
+    fn closed_error(&self) -> Error {
+        self.process.take_error().unwrap_or(Error::ChildGone)
+    }
+
+    /// Returns the next output, `None` once the output has ended.
+    fn poll_output(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<Vec<u8>>>> {
+        if !self.pending.is_empty() {
+            return Poll::Ready(Some(Ok(std::mem::take(&mut self.pending))));
+        }
+
+        match ready!(self.output.poll_recv(cx)) {
+            Some(chunk) => Poll::Ready(Some(Ok(chunk))),
+            // the output also ends if reading failed
+            None => Poll::Ready(self.process.take_error().map(Err)),
+        }
+    }
+
+    /// Waits until the io task has taken all queued input by reserving the whole capacity of the channel.
+    fn poll_flush_input(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let flush = match &mut self.flush {
+            Some(flush) => flush,
+            None => {
+                // a permit reserved for the next write would never be released
+                self.input.abort_send();
+                let Some(sender) = self.input.get_ref().cloned() else {
+                    // closed by a shutdown, which flushed before
+                    return Poll::Ready(Ok(()));
+                };
+
+                self.flush.insert(ReusableBoxFuture::new(async move {
+                    sender.reserve_many(sender.max_capacity()).await.is_ok()
+                }))
+            }
+        };
+
+        let flushed = ready!(flush.poll(cx));
+        self.flush = None;
+
+        if flushed {
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Ready(Err(self.closed_error()))
+        }
+    }
+
+    fn poll_shutdown_input(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        ready!(self.poll_flush_input(cx))?;
+        self.input.close();
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncRead for PtyStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        let mut chunk = match ready!(this.poll_output(cx)) {
+            Some(Ok(chunk)) => chunk,
+            Some(Err(err)) => return Poll::Ready(Err(into_io_error(err))),
+            None => return Poll::Ready(Ok(())),
+        };
+
+        let len = chunk.len().min(buf.remaining());
+        buf.put_slice(&chunk[..len]);
+        chunk.drain(..len);
+        this.pending = chunk;
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for PtyStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(Sink::<Vec<u8>>::poll_ready(Pin::new(&mut *this), cx)).map_err(into_io_error)?;
+        Sink::start_send(Pin::new(&mut *this), buf.to_vec()).map_err(into_io_error)?;
+
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().poll_flush_input(cx).map_err(into_io_error)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut()
+            .poll_shutdown_input(cx)
+            .map_err(into_io_error)
+    }
+}
+
+impl Stream for PtyStream {
+    type Item = Result<Vec<u8>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().poll_output(cx)
+    }
+}
+
+impl Sink<Vec<u8>> for PtyStream {
+    type Error = Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let this = self.get_mut();
+        match ready!(this.input.poll_reserve(cx)) {
+            Ok(()) => Poll::Ready(Ok(())),
+            Err(_) => Poll::Ready(Err(this.closed_error())),
+        }
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Vec<u8>) -> Result<()> {
+        let this = self.get_mut();
+        this.input
+            .send_item(TerminalInput::Input(item))
+            .map_err(|_| this.closed_error())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().poll_flush_input(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().poll_shutdown_input(cx)
+    }
+}
+
+impl ResizeHandle {
+    pub async fn resize(&self, size: TerminalSize) -> Result<()> {
+        self.write
+            .send(TerminalInput::Resize(size))
+            .await
+            .map_err(|_| self.closed_error())
+    }
+
+    pub fn try_resize(&self, size: TerminalSize) -> Result<()> {
+        self.write
+            .try_send(TerminalInput::Resize(size))
+            .map_err(|err| match err {
+                mpsc::error::TrySendError::Full(_) => Error::ChannelFull,
+                mpsc::error::TrySendError::Closed(_) => self.closed_error(),
+            })
+    }
+
+    fn closed_error(&self) -> Error {
+        self.failure
+            .lock()
+            .unwrap()
+            .take()
+            .unwrap_or(Error::ChildGone)
+    }
+}
+
+fn into_io_error(err: Error) -> io::Error {
+    match err {
+        Error::Read(err) | Error::Write(err) => err,
+        Error::ChildGone => io::Error::new(io::ErrorKind::BrokenPipe, err),
+        err => io::Error::other(err),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+    use crate::Command;
+
+    async fn cat() -> PtyStream {
+        let (process, output) = PtyProcess::spawn(Command::new("cat")).await.unwrap();
+        process.into_stream(output)
+    }
+
+    /// Reads until `expected` was part of the output, returns everything read.
+    async fn read_until(stream: &mut PtyStream, expected: &str) -> String {
+        let mut output = Vec::new();
+        let mut buf = [0; 4];
+        while !String::from_utf8_lossy(&output).contains(expected) {
+            let len = stream.read(&mut buf).await.unwrap();
+            assert_ne!(len, 0, "output ended before {expected:?}");
+            output.extend_from_slice(&buf[..len]);
+        }
+
+        String::from_utf8(output).unwrap()
+    }
+
+    #[tokio::test]
+    async fn read_write_eof() {
+        tokio::time::timeout(Duration::from_secs(10), async {
+            let mut stream = cat().await;
+
+            stream.write_all(b"hello\n").await.unwrap();
+            stream.flush().await.unwrap();
+            // the pty echoes the input, then cat writes it back
+            let output = read_until(&mut stream, "hello\r\nhello\r\n").await;
+            assert_eq!(output, "hello\r\nhello\r\n");
+
+            // Ctrl+D on an empty line ends the input of cat
+            stream.write_all(b"\x04").await.unwrap();
+            let mut rest = Vec::new();
+            stream.read_to_end(&mut rest).await.unwrap();
+
+            let status = stream.process().exited().await.unwrap();
+            assert!(status.success());
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn flush_and_shutdown() {
+        tokio::time::timeout(Duration::from_secs(10), async {
+            let mut stream = cat().await;
+
+            // more writes than the input channel holds
+            for _ in 0..50 {
+                stream.write_all(b"line\n").await.unwrap();
+            }
+            stream.flush().await.unwrap();
+            stream.shutdown().await.unwrap();
+
+            let err = stream.write_all(b"x").await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+            // flushing a closed stream has nothing to wait for
+            stream.flush().await.unwrap();
+
+            // the output is still readable
+            read_until(&mut stream, &"line\r\n".repeat(50)).await;
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn stream_and_sink() {
+        use std::future::poll_fn;
+
+        tokio::time::timeout(Duration::from_secs(10), async {
+            let mut stream = cat().await;
+
+            poll_fn(|cx| Sink::<Vec<u8>>::poll_ready(Pin::new(&mut stream), cx))
+                .await
+                .unwrap();
+            Pin::new(&mut stream)
+                .start_send(b"chunk\n".to_vec())
+                .unwrap();
+
+            let mut output = String::new();
+            while !output.contains("chunk\r\nchunk\r\n") {
+                let chunk = poll_fn(|cx| Pin::new(&mut stream).poll_next(cx))
+                    .await
+                    .unwrap()
+                    .unwrap();
+                output.push_str(std::str::from_utf8(&chunk).unwrap());
+            }
+
+            stream.process().kill().unwrap();
+            while poll_fn(|cx| Pin::new(&mut stream).poll_next(cx))
+                .await
+                .is_some()
+            {}
+        })
+        .await
+        .unwrap();
+    }
+}
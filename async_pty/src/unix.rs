@@ -0,0 +1,126 @@
+use std::{
+    io,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+};
+
+use portable_pty::{Child, ExitStatus, MasterPty};
+use tokio::io::unix::AsyncFd;
+
+/// The master side of a pty, driven by the tokio reactor instead of blocking threads.
+pub(crate) struct AsyncPty {
+    fd: AsyncFd<OwnedFd>,
+}
+
+impl AsyncPty {
+    /// Registers a non-blocking duplicate of the master fd with the reactor.
+    ///
+    /// Fails outside of a tokio runtime. Like all tokio io types, this panics if the runtime has io disabled.
+    pub(crate) fn new(master: &dyn MasterPty) -> io::Result<Self> {
+        tokio::runtime::Handle::try_current().map_err(io::Error::other)?;
+
+        let master_fd = master
+            .as_raw_fd()
+            .ok_or_else(|| io::Error::other("pty has no file descriptor"))?;
+
+        let fd = unsafe { libc::fcntl(master_fd, libc::F_DUPFD_CLOEXEC, 0) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        // the flag is shared with the original fd, which we don't read from or write to anymore
+        let flags = unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_GETFL) };
+        if flags < 0
+            || unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_SETFL, flags | libc::O_NONBLOCK) } < 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+
+        match AsyncFd::new(fd) {
+            Ok(fd) => Ok(Self { fd }),
+            Err(err) => {
+                // the threaded fallback needs blocking io
+                unsafe { libc::fcntl(master_fd, libc::F_SETFL, flags) };
+                Err(err)
+            }
+        }
+    }
+
+    /// Appends to `buffer`, reading at most as many bytes as its spare capacity holds.
+    pub(crate) async fn read(&self, buffer: &mut Vec<u8>) -> io::Result<usize> {
+        loop {
+            let mut guard = self.fd.readable().await?;
+            let result = guard.try_io(|fd| {
+                let spare = buffer.spare_capacity_mut();
+                let bytes =
+                    unsafe { libc::read(fd.as_raw_fd(), spare.as_mut_ptr().cast(), spare.len()) };
+                if bytes < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+
+                let bytes = bytes as usize;
+                // the read initialized these bytes
+                unsafe { buffer.set_len(buffer.len() + bytes) };
+                Ok(bytes)
+            });
+
+            if let Ok(result) = result {
+                return result;
+            }
+        }
+    }
+
+    pub(crate) async fn write_all(&self, mut buffer: &[u8]) -> io::Result<()> {
+        while !buffer.is_empty() {
+            let mut guard = self.fd.writable().await?;
+            let result = guard.try_io(|fd| {
+                let bytes =
+                    unsafe { libc::write(fd.as_raw_fd(), buffer.as_ptr().cast(), buffer.len()) };
+                if bytes < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+
+                Ok(bytes as usize)
+            });
+
+            match result {
+                Ok(Ok(0)) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(Ok(bytes)) => buffer = &buffer[bytes..],
+                Ok(Err(err)) if err.kind() == io::ErrorKind::Interrupted => {}
+                Ok(Err(err)) => return Err(err),
+                // spurious readiness
+                Err(_) => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Waits for the child to exit.
+///
+/// On Linux this waits on a pidfd, elsewhere it needs a thread of the blocking pool.
+pub(crate) async fn wait(mut child: Box<dyn Child + Send + Sync>) -> io::Result<ExitStatus> {
+    #[cfg(target_os = "linux")]
+    if let Some(pid) = child.process_id()
+        && let Ok(pidfd) = pidfd_open(pid)
+    {
+        // the pidfd becomes readable once the child has exited
+        let _ = pidfd.readable().await?;
+        return child.wait();
+    }
+
+    tokio::task::spawn_blocking(move || child.wait())
+        .await
+        .map_err(io::Error::other)?
+}
+
+#[cfg(target_os = "linux")]
+fn pidfd_open(pid: u32) -> io::Result<AsyncFd<OwnedFd>> {
+    let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid as libc::pid_t, 0) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    AsyncFd::new(unsafe { OwnedFd::from_raw_fd(fd as libc::c_int) })
+}
@@ -43,7 +43,11 @@ impl UI {
                 match action {
                     local_terminal::Action::Run(task) => task.map(Message::Terminal),
                     local_terminal::Action::IdChanged => Task::none(),
-                    local_terminal::Action::Close => iced::exit(),
+                    local_terminal::Action::OpenUrl(url) => {
+                        println!("Opening {url}");
+                        Task::none()
+                    }
+                    local_terminal::Action::Close { .. } => iced::exit(),
                     local_terminal::Action::None => Task::none(),
                 }
             }
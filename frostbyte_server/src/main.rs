@@ -0,0 +1,667 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    io,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU32, Ordering},
+    },
+    time::Duration,
+};
+
+use async_pty::{
+    Command, ExitStatus, PtyProcess,
+    remote::{Address, Connection, Frame, Listener, SessionInfo, read_frame, write_frame},
+};
+use tokio::sync::mpsc;
+
+const USAGE: &str =
+    "usage: frostbyte_server [--persist] [--allow-remote] [tcp:<host>:<port> | unix:<path>]
+
+Listens on unix:$XDG_RUNTIME_DIR/frostbyte.sock by default.
+Over TCP clients have to send the token set in FROSTBYTE_TOKEN.";
+
+/// How long a client connecting over TCP has to send its token.
+const AUTH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How much output of a session is kept to redraw the screen of a client which attaches later.
+///
+/// Only the raw output is kept, so once older output was dropped a client misses the modes it set,
+/// e.g. a full screen application on the alternate screen is only shown correctly after it redraws.
+const SCROLLBACK_LEN: usize = 1024 * 1024;
+
+/// How far past the limit the scrollback is searched for a line break or escape sequence to cut at.
+const SCROLLBACK_CUT_SEARCH_LEN: usize = 4096;
+
+#[tokio::main]
+async fn main() {
+    let mut persist = false;
+    let mut allow_remote = false;
+    let mut address = None;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--persist" => persist = true,
+            "--allow-remote" => allow_remote = true,
+            _ if address.is_none() && !arg.starts_with("--") => address = Some(arg),
+            _ => {
+                eprintln!("{USAGE}");
+                std::process::exit(2);
+            }
+        }
+    }
+
+    let address = address.or_else(|| {
+        let runtime_dir = std::env::var("XDG_RUNTIME_DIR").ok()?;
+        Some(format!("unix:{runtime_dir}/frostbyte.sock"))
+    });
+    let Some(address) = address else {
+        eprintln!("XDG_RUNTIME_DIR is not set, pass the address to listen on\n{USAGE}");
+        std::process::exit(2);
+    };
+
+    let address: Address = match address.parse() {
+        Ok(address) => address,
+        Err(err) => {
+            eprintln!("{err}\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
+
+    // everyone who knows the token gets a shell, so it must not show up in the process list
+    let token = match &address {
+        Address::Tcp { token: Some(_), .. } => {
+            eprintln!("Pass the token in FROSTBYTE_TOKEN instead of the address");
+            std::process::exit(2);
+        }
+        Address::Tcp { token: None, .. } => match std::env::var("FROSTBYTE_TOKEN") {
+            Ok(token) if !token.is_empty() => Some(Arc::from(token)),
+            _ => {
+                eprintln!("FROSTBYTE_TOKEN must be set to listen on {address}");
+                std::process::exit(2);
+            }
+        },
+        #[cfg(unix)]
+        Address::Unix(_) => None,
+    };
+
+    let listener = match Listener::bind(&address).await {
+        Ok(listener) => listener,
+        Err(err) => {
+            eprintln!("Error listening on {address}: {err}");
+            std::process::exit(1);
+        }
+    };
+
+    if let Listener::Tcp(tcp_listener) = &listener
+        && let Ok(local_address) = tcp_listener.local_addr()
+        && !local_address.ip().is_loopback()
+        && !allow_remote
+    {
+        eprintln!(
+            "{local_address} is not a loopback address, pass --allow-remote to listen on it anyway"
+        );
+        std::process::exit(2);
+    }
+
+    eprintln!("Listening on {address}");
+
+    let sessions = Arc::new(Sessions::new(persist, shell));
+    run(listener, sessions, token).await;
+}
+
+/// Serves the clients of `listener`, which have to send `token` first if set.
+async fn run(listener: Listener, sessions: Arc<Sessions>, token: Option<Arc<str>>) {
+    loop {
+        match listener.accept().await {
+            Ok(mut connection) => {
+                let sessions = sessions.clone();
+                let token = token.clone();
+                tokio::spawn(async move {
+                    let result = match &token {
+                        Some(token) => authenticate(&mut connection, token).await,
+                        None => Ok(()),
+                    };
+
+                    if let Err(err) = result {
+                        eprintln!("Error authenticating client: {err}");
+                    } else if let Err(err) = serve(connection, sessions).await {
+                        eprintln!("Error serving session: {err}");
+                    }
+                });
+            }
+            Err(err) => eprintln!("Error accepting connection: {err}"),
+        }
+    }
+}
+
+/// Waits for the client to send the right token.
+async fn authenticate(connection: &mut Connection, token: &str) -> io::Result<()> {
+    let frame = tokio::time::timeout(AUTH_TIMEOUT, read_frame(connection))
+        .await
+        .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))??;
+
+    match frame {
+        Some(Frame::Auth(received)) if tokens_match(&received, token.as_bytes()) => Ok(()),
+        _ => Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "the client sent no or a wrong token",
+        )),
+    }
+}
+
+/// Compares the tokens in constant time, so the token can't be guessed byte by byte.
+fn tokens_match(received: &[u8], token: &[u8]) -> bool {
+    received.len() == token.len()
+        && received
+            .iter()
+            .zip(token)
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+/// The shells of the server.
+///
+/// Unless the server persists sessions, a shell is only reachable by the client which started it
+/// and is hung up once that client is gone.
+struct Sessions {
+    persist: bool,
+    // the command new sessions run
+    shell: fn() -> Command,
+    sessions: Mutex<BTreeMap<u32, Arc<Session>>>,
+    next_id: AtomicU32,
+}
+
+impl Sessions {
+    fn new(persist: bool, shell: fn() -> Command) -> Self {
+        Self {
+            persist,
+            shell,
+            sessions: Mutex::default(),
+            next_id: AtomicU32::new(1),
+        }
+    }
+
+    fn list(&self) -> Vec<SessionInfo> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .values()
+            .map(|session| SessionInfo {
+                id: session.id,
+                name: session
+                    .process
+                    .foreground_process()
+                    .map(|info| info.name)
+                    .unwrap_or_default(),
+            })
+            .collect()
+    }
+
+    fn get(&self, id: u32) -> Option<Arc<Session>> {
+        self.sessions.lock().unwrap().get(&id).cloned()
+    }
+
+    fn remove(&self, id: u32) {
+        self.sessions.lock().unwrap().remove(&id);
+    }
+
+    /// Hangs up the shell of a session, an attached client still receives its exit status.
+    fn kill(&self, id: u32) {
+        let session = self.sessions.lock().unwrap().remove(&id);
+        if let Some(session) = session {
+            // signals would only reach the job in the foreground, not the shell itself,
+            // once the shell is gone the kernel hangs up its jobs and the output ends
+            let _ = session.process.kill();
+        }
+    }
+
+    async fn spawn(self: &Arc<Self>, command: Command) -> io::Result<Arc<Session>> {
+        let (process, mut output) = PtyProcess::spawn(command).await.map_err(io::Error::other)?;
+
+        let session = Arc::new(Session {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            process,
+            state: Mutex::default(),
+        });
+
+        if self.persist {
+            self.sessions
+                .lock()
+                .unwrap()
+                .insert(session.id, session.clone());
+        }
+
+        // only holds a weak reference, so the process is dropped and hung up once nobody uses the session
+        let weak = Arc::downgrade(&session);
+        let sessions = self.clone();
+        tokio::spawn(async move {
+            while let Some(chunk) = output.recv().await {
+                let Some(session) = weak.upgrade() else {
+                    return;
+                };
+                session.output(chunk);
+            }
+
+            let Some(session) = weak.upgrade() else {
+                return;
+            };
+            let status = session.process.exited().await;
+            session.exit(status);
+            sessions.remove(session.id);
+        });
+
+        Ok(session)
+    }
+}
+
+struct Session {
+    id: u32,
+    process: PtyProcess,
+    state: Mutex<SessionState>,
+}
+
+#[derive(Default)]
+struct SessionState {
+    scrollback: VecDeque<u8>,
+    // the attached client, a session has at most one
+    client: Option<mpsc::UnboundedSender<Frame>>,
+    exited: Option<Option<ExitStatus>>,
+}
+
+impl SessionState {
+    fn send(&mut self, frame: Frame) {
+        if let Some(client) = &self.client
+            && client.send(frame).is_err()
+        {
+            self.client = None;
+        }
+    }
+}
+
+impl Session {
+    /// Returns the frames for a new client, starting with the scrollback.
+    ///
+    /// A client which was attached before is detached.
+    fn attach(&self) -> mpsc::UnboundedReceiver<Frame> {
+        let mut state = self.state.lock().unwrap();
+        let (sender, receiver) = mpsc::unbounded_channel();
+
+        let _ = sender.send(Frame::Attached(self.id));
+        if !state.scrollback.is_empty() {
+            let scrollback = state.scrollback.make_contiguous().to_vec();
+            let _ = sender.send(Frame::Output(scrollback));
+        }
+        if let Some(status) = &state.exited {
+            let _ = sender.send(Frame::Exit(status.clone()));
+        }
+
+        state.client = Some(sender);
+        receiver
+    }
+
+    fn output(&self, chunk: Vec<u8>) {
+        let mut state = self.state.lock().unwrap();
+
+        state.scrollback.extend(&chunk);
+        let excess = state.scrollback.len().saturating_sub(SCROLLBACK_LEN);
+        if excess > 0 {
+            let cut = scrollback_cut(&state.scrollback, excess);
+            state.scrollback.drain(..cut);
+        }
+
+        state.send(Frame::Output(chunk));
+    }
+
+    fn exit(&self, status: Option<ExitStatus>) {
+        let mut state = self.state.lock().unwrap();
+        state.exited = Some(status.clone());
+        state.send(Frame::Exit(status));
+    }
+}
+
+/// Returns where to cut off at least `excess` bytes of the scrollback,
+/// so the replay doesn't start in the middle of an escape sequence or character.
+fn scrollback_cut(scrollback: &VecDeque<u8>, excess: usize) -> usize {
+    let boundary = scrollback
+        .range(excess..)
+        .take(SCROLLBACK_CUT_SEARCH_LEN)
+        .position(|&byte| byte == b'\n' || byte == 0x1b);
+
+    match boundary {
+        // after a line break, but before an escape sequence
+        Some(offset) if scrollback[excess + offset] == b'\n' => excess + offset + 1,
+        Some(offset) => excess + offset,
+        // a very long line, at least skip the rest of a split character
+        None => {
+            excess
+                + scrollback
+                    .range(excess..)
+                    .take_while(|&&byte| byte & 0b1100_0000 == 0b1000_0000)
+                    .count()
+        }
+    }
+}
+
+/// Sessions run login shells, like a shell started over SSH.
+fn shell() -> Command {
+    Command::shell()
+        .login(true)
+        .term_program("frostbyte", env!("CARGO_PKG_VERSION"))
+}
+
+/// Connects the client to a new or existing session until either of them is gone.
+async fn serve(connection: Connection, sessions: Arc<Sessions>) -> io::Result<()> {
+    let (mut reader, mut writer) = tokio::io::split(connection);
+
+    let first = loop {
+        match read_frame(&mut reader).await? {
+            Some(Frame::List) => {
+                let list = Frame::Sessions(sessions.list());
+                write_frame(&mut writer, &list).await?;
+            }
+            Some(frame) => break frame,
+            None => return Ok(()),
+        }
+    };
+
+    let session = match first {
+        Frame::Kill(id) => {
+            sessions.kill(id);
+            return Ok(());
+        }
+        Frame::Attach(id) => match sessions.get(id) {
+            Some(session) => session,
+            None => return write_frame(&mut writer, &Frame::Exit(None)).await,
+        },
+        // any other frame starts a new session
+        Frame::Resize(size) => sessions.spawn((sessions.shell)().size(size)).await?,
+        Frame::Input(input) => {
+            let session = sessions.spawn((sessions.shell)()).await?;
+            let _ = session.process.write(input).await;
+            session
+        }
+        _ => sessions.spawn((sessions.shell)()).await?,
+    };
+
+    let mut frames = session.attach();
+
+    // reading frames is not cancel safe, so it gets its own task instead of being part of the select
+    let input_session = session.clone();
+    let mut input = AbortOnDrop(tokio::spawn(async move {
+        while let Some(frame) = read_frame(&mut reader).await? {
+            // errors surface as the end of the output
+            let _ = match frame {
+                Frame::Input(input) => input_session.process.write(input).await,
+                Frame::Resize(size) => input_session.process.resize(size).await,
+                // the session is dropped together with the connection
+                Frame::Close => {
+                    sessions.remove(input_session.id);
+                    break;
+                }
+                _ => Ok(()),
+            };
+        }
+
+        io::Result::Ok(())
+    }));
+
+    loop {
+        tokio::select! {
+            frame = frames.recv() => match frame {
+                Some(frame) => {
+                    write_frame(&mut writer, &frame).await?;
+                    if let Frame::Exit(_) = frame {
+                        return Ok(());
+                    }
+                }
+                // another client attached to the session
+                None => return Ok(()),
+            },
+            // the client is gone, the session is hung up unless it persists
+            result = &mut input.0 => return result.map_err(io::Error::other)?,
+        }
+    }
+}
+
+/// Aborts the task once dropped, so it can't outlive the connection it serves.
+struct AbortOnDrop<T>(tokio::task::JoinHandle<T>);
+
+impl<T> Drop for AbortOnDrop<T> {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::time::Duration;
+
+    use async_pty::{
+        TerminalSize,
+        remote::{Listener, list_sessions},
+    };
+
+    use super::*;
+
+    /// Starts a server running `sh` on a unix socket only used by this test.
+    async fn start(name: &str) -> Address {
+        let path =
+            std::env::temp_dir().join(format!("frostbyte-{name}-{}.sock", std::process::id()));
+        let address = Address::Unix(path);
+        let listener = Listener::bind(&address).await.unwrap();
+
+        let sessions = Arc::new(Sessions::new(true, || Command::new("sh")));
+        tokio::spawn(run(listener, sessions, None));
+
+        address
+    }
+
+    async fn next_frame(connection: &mut Connection) -> Option<Frame> {
+        tokio::time::timeout(Duration::from_secs(10), read_frame(connection))
+            .await
+            .expect("no frame was received")
+            .unwrap()
+    }
+
+    /// Reads output until it contains `expected`.
+    async fn expect_output(connection: &mut Connection, expected: &str) {
+        let mut output = String::new();
+        while !output.contains(expected) {
+            match next_frame(connection).await {
+                Some(Frame::Output(chunk)) => output.push_str(&String::from_utf8_lossy(&chunk)),
+                Some(_) => {}
+                None => panic!("connection closed before {expected:?}, got {output:?}"),
+            }
+        }
+    }
+
+    fn size(cols: u16, rows: u16) -> Frame {
+        Frame::Resize(TerminalSize {
+            cols,
+            rows,
+            ..Default::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn attach_input_resize() {
+        let address = start("attach").await;
+
+        let mut first = Connection::connect(&address).await.unwrap();
+        write_frame(&mut first, &size(120, 40)).await.unwrap();
+        let Some(Frame::Attached(id)) = next_frame(&mut first).await else {
+            panic!("expected the session id");
+        };
+
+        write_frame(&mut first, &Frame::Input(b"stty size\n".to_vec()))
+            .await
+            .unwrap();
+        expect_output(&mut first, "40 120").await;
+
+        write_frame(&mut first, &size(100, 30)).await.unwrap();
+        write_frame(&mut first, &Frame::Input(b"stty size\n".to_vec()))
+            .await
+            .unwrap();
+        expect_output(&mut first, "30 100").await;
+
+        let sessions = list_sessions(&address).await.unwrap();
+        assert!(sessions.iter().any(|session| session.id == id));
+
+        // attaching replays the output and detaches the first client
+        let mut second = Connection::connect(&address).await.unwrap();
+        write_frame(&mut second, &Frame::Attach(id)).await.unwrap();
+        assert!(
+            matches!(next_frame(&mut second).await, Some(Frame::Attached(attached)) if attached == id)
+        );
+        expect_output(&mut second, "30 100").await;
+        while next_frame(&mut first).await.is_some() {}
+
+        write_frame(&mut second, &Frame::Input(b"exit 3\n".to_vec()))
+            .await
+            .unwrap();
+        let status = loop {
+            match next_frame(&mut second).await {
+                Some(Frame::Exit(status)) => break status,
+                Some(_) => {}
+                None => panic!("connection closed before the exit status"),
+            }
+        };
+        assert_eq!(status.unwrap().exit_code(), 3);
+
+        assert!(list_sessions(&address).await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn scrollback_is_cut_at_a_boundary() {
+        let scrollback: VecDeque<u8> = b"abc\x1b[1mdef\nghi".iter().copied().collect();
+        // before the escape sequence
+        assert_eq!(scrollback_cut(&scrollback, 1), 3);
+        // after the line break
+        assert_eq!(scrollback_cut(&scrollback, 5), 11);
+
+        let scrollback: VecDeque<u8> = "äöü".bytes().collect();
+        assert_eq!(scrollback_cut(&scrollback, 1), 2);
+        assert_eq!(scrollback_cut(&scrollback, 2), 2);
+    }
+
+    #[tokio::test]
+    async fn close_sessions() {
+        let address = start("close").await;
+
+        // a session without a client
+        let mut detached = Connection::connect(&address).await.unwrap();
+        write_frame(&mut detached, &size(80, 24)).await.unwrap();
+        let Some(Frame::Attached(detached_id)) = next_frame(&mut detached).await else {
+            panic!("expected the session id");
+        };
+        drop(detached);
+
+        let mut attached = Connection::connect(&address).await.unwrap();
+        write_frame(&mut attached, &size(80, 24)).await.unwrap();
+        let Some(Frame::Attached(attached_id)) = next_frame(&mut attached).await else {
+            panic!("expected the session id");
+        };
+
+        async_pty::remote::close_session(&address, detached_id)
+            .await
+            .unwrap();
+        async_pty::remote::close_session(&address, attached_id)
+            .await
+            .unwrap();
+        assert!(list_sessions(&address).await.unwrap().is_empty());
+
+        // the attached client learns that the shell is gone
+        loop {
+            match next_frame(&mut attached).await {
+                Some(Frame::Exit(_)) => break,
+                Some(_) => {}
+                None => panic!("connection closed before the exit status"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn close_a_session_running_a_job() {
+        let address = start("job").await;
+
+        let mut connection = Connection::connect(&address).await.unwrap();
+        write_frame(&mut connection, &Frame::Input(b"sleep 100\n".to_vec()))
+            .await
+            .unwrap();
+        let Some(Frame::Attached(id)) = next_frame(&mut connection).await else {
+            panic!("expected the session id");
+        };
+
+        // wait until the job is in the foreground instead of the shell
+        loop {
+            let sessions = list_sessions(&address).await.unwrap();
+            if sessions.iter().any(|session| session.name == "sleep") {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        async_pty::remote::close_session(&address, id)
+            .await
+            .unwrap();
+
+        let exit = tokio::time::timeout(Duration::from_secs(3), async {
+            loop {
+                match read_frame(&mut connection).await.unwrap() {
+                    Some(Frame::Exit(_)) => break,
+                    Some(_) => {}
+                    None => panic!("connection closed before the exit status"),
+                }
+            }
+        });
+        exit.await.expect("the shell was not hung up");
+    }
+
+    #[tokio::test]
+    async fn attach_to_unknown_session() {
+        let address = start("unknown").await;
+
+        let mut connection = Connection::connect(&address).await.unwrap();
+        write_frame(&mut connection, &Frame::Attach(42))
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            next_frame(&mut connection).await,
+            Some(Frame::Exit(None))
+        ));
+        assert!(next_frame(&mut connection).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn tcp_clients_need_the_token() {
+        let listener = Listener::bind(&"tcp:127.0.0.1:0".parse().unwrap())
+            .await
+            .unwrap();
+        let Listener::Tcp(tcp_listener) = &listener else {
+            panic!("expected a tcp listener");
+        };
+        let port = tcp_listener.local_addr().unwrap().port();
+
+        let sessions = Arc::new(Sessions::new(true, || Command::new("sh")));
+        tokio::spawn(run(listener, sessions, Some(Arc::from("secret"))));
+
+        let address: Address = format!("tcp:secret@127.0.0.1:{port}").parse().unwrap();
+        assert!(list_sessions(&address).await.unwrap().is_empty());
+
+        for address in [
+            format!("tcp:wrong@127.0.0.1:{port}"),
+            format!("tcp:127.0.0.1:{port}"),
+        ] {
+            let address: Address = address.parse().unwrap();
+            let mut connection = Connection::connect(&address).await.unwrap();
+            write_frame(&mut connection, &Frame::List).await.unwrap();
+            // the server hangs up without replying
+            assert!(!matches!(
+                tokio::time::timeout(Duration::from_secs(10), read_frame(&mut connection))
+                    .await
+                    .expect("the server didn't hang up"),
+                Ok(Some(_))
+            ));
+        }
+    }
+}
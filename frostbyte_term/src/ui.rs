@@ -1,4 +1,4 @@
-use std::{collections::BTreeMap, fmt::Debug};
+use std::{collections::BTreeMap, fmt::Debug, time::Duration};
 
 #[cfg(target_os = "linux")]
 use std::sync::{
@@ -11,7 +11,11 @@ use signal_hook::consts::signal::SIGUSR1;
 #[cfg(target_os = "linux")]
 use signal_hook::flag as signal_flag;
 
-use frozen_term::local_terminal::{self, LocalTerminal};
+use async_pty::remote::{Address, SessionInfo};
+use frozen_term::{
+    local_terminal::{self, Command, LocalTerminal, ShellPool},
+    remote_terminal::{self, RemoteTerminal},
+};
 use global_hotkey::{GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState, hotkey};
 use iced::{
     Alignment::Center,
@@ -35,9 +39,15 @@ pub enum Message {
         id: u32,
         message: local_terminal::Message,
     },
+    RemoteTerminal {
+        id: u32,
+        message: remote_terminal::Message,
+    },
+    SessionsListed(Vec<SessionInfo>),
     OpenTab,
     SwitchTab(u32),
     CloseTab(u32),
+    ToggleRecording(u32),
     Hotkey,
     WindowOpened(window::Id),
     CloseWindow,
@@ -49,8 +59,12 @@ pub enum Message {
     UpdateMonitor(MonitorIndex),
     PreviousTab,
     NextTab,
+    RefreshProcessNames,
 }
 
+/// How often the names of the foreground processes shown in the tab bar are updated.
+const PROCESS_NAME_INTERVAL: Duration = Duration::from_secs(1);
+
 enum Mode {
     Winit,
     #[cfg(target_os = "linux")]
@@ -59,8 +73,43 @@ enum Mode {
 
 const ICON: &'static [u8] = include_bytes!("../assets/icon.png");
 
+/// A tab either runs its shell itself or on a `frostbyte_server`.
+enum Tab {
+    Local(LocalTerminal),
+    Remote(RemoteTerminal),
+}
+
+impl Tab {
+    fn focus<T>(&self) -> Task<T>
+    where
+        T: Send + 'static,
+    {
+        match self {
+            Self::Local(terminal) => terminal.focus(),
+            Self::Remote(terminal) => terminal.focus(),
+        }
+    }
+
+    fn view(&self, id: u32) -> Element<'_, Message> {
+        match self {
+            Self::Local(terminal) => terminal
+                .view()
+                .map(move |message| Message::LocalTerminal { id, message }),
+            Self::Remote(terminal) => terminal
+                .view()
+                .map(move |message| Message::RemoteTerminal { id, message }),
+        }
+    }
+}
+
 pub struct UI {
-    terminals: BTreeMap<u32, LocalTerminal>,
+    terminals: BTreeMap<u32, Tab>,
+    // the foreground process of each local tab, querying it is too slow to do on every view
+    process_names: BTreeMap<u32, String>,
+    // shells run on this server instead of inside frostbyte, so they survive restarts
+    session_server: Option<Address>,
+    // keeps a shell warm for the next local tab
+    shell_pool: ShellPool,
     window_id: Option<window::Id>,
     selected_tab: u32,
     new_terminal_id: u32,
@@ -126,6 +175,49 @@ impl UI {
 
         let terminals = BTreeMap::new();
 
+        let session_server =
+            std::env::var("FROSTBYTE_SERVER").ok().and_then(|address| {
+                match address.parse::<Address>() {
+                    Ok(address) => Some(address),
+                    Err(err) => {
+                        eprintln!("Ignoring FROSTBYTE_SERVER: {}", err);
+                        None
+                    }
+                }
+            });
+
+        // reattach to the sessions left behind by the last run
+        let task = match &session_server {
+            Some(address) => {
+                let address = address.clone();
+                Task::future(async move {
+                    match async_pty::remote::list_sessions(&address).await {
+                        Ok(sessions) => sessions,
+                        Err(err) => {
+                            eprintln!("Error listing sessions of {}: {}", address, err);
+                            Vec::new()
+                        }
+                    }
+                })
+                .map(Message::SessionsListed)
+            }
+            None => Task::none(),
+        };
+
+        // the waiting shell is only used for tabs opened in its directory, usually the home directory
+        let command = Command::shell().term_program("frostbyte", env!("CARGO_PKG_VERSION"));
+        let command = match std::env::var_os("HOME") {
+            Some(home) => command.cwd(home),
+            None => command,
+        };
+        let shell_pool = ShellPool::new(command, 1);
+        let task = if session_server.is_none() {
+            let pool = shell_pool.clone();
+            Task::batch([task, Task::future(async move { pool.fill() }).discard()])
+        } else {
+            task
+        };
+
         let hotkey = Hotkey::default();
         let global_hotkey = hotkey.global_hotkey();
         let hotkey_id = global_hotkey.id;
@@ -135,6 +227,9 @@ impl UI {
         (
             Self {
                 terminals,
+                process_names: BTreeMap::new(),
+                session_server,
+                shell_pool,
                 window_id: None,
                 selected_tab: 1,
                 new_terminal_id: 1,
@@ -145,7 +240,7 @@ impl UI {
                 mode,
                 monitor: MonitorIndex(0),
             },
-            Task::none(),
+            task,
         )
     }
 
@@ -153,21 +248,69 @@ impl UI {
         match message {
             Message::LocalTerminal { id, message } => {
                 let term = match self.terminals.get_mut(&id) {
-                    None => return Task::none(),
-                    Some(term) => term,
+                    Some(Tab::Local(term)) => term,
+                    _ => return Task::none(),
                 };
 
                 let action = term.update(message);
 
+                if let Some(err) = term.take_recording_error() {
+                    eprintln!("Error writing recording, it was stopped: {}", err);
+                }
+
                 match action {
-                    local_terminal::Action::Close => self.close_tab(id),
+                    // keep failed processes open so the exit message stays readable
+                    local_terminal::Action::Close { status } => {
+                        if status.is_some_and(|status| status.success()) {
+                            self.close_tab(id)
+                        } else {
+                            Task::none()
+                        }
+                    }
                     local_terminal::Action::Run(task) => {
                         task.map(move |message| Message::LocalTerminal { id, message })
                     }
                     local_terminal::Action::IdChanged => self.focus_tab(),
+                    local_terminal::Action::OpenUrl(url) => open_url(url),
                     local_terminal::Action::None => Task::none(),
                 }
             }
+            Message::RemoteTerminal { id, message } => {
+                let term = match self.terminals.get_mut(&id) {
+                    Some(Tab::Remote(term)) => term,
+                    _ => return Task::none(),
+                };
+
+                match term.update(message) {
+                    // keep the tab open if the connection was lost, so the message stays readable
+                    remote_terminal::Action::Close => {
+                        if term.exit_status().is_some_and(|status| status.success()) {
+                            self.close_tab(id)
+                        } else {
+                            Task::none()
+                        }
+                    }
+                    remote_terminal::Action::Run(task) => {
+                        task.map(move |message| Message::RemoteTerminal { id, message })
+                    }
+                    remote_terminal::Action::IdChanged => self.focus_tab(),
+                    remote_terminal::Action::OpenUrl(url) => open_url(url),
+                    remote_terminal::Action::None => Task::none(),
+                }
+            }
+            Message::SessionsListed(sessions) => {
+                let Some(address) = self.session_server.clone() else {
+                    return Task::none();
+                };
+
+                Task::batch(sessions.into_iter().map(|session| {
+                    let (terminal, task) =
+                        RemoteTerminal::attach(address.clone(), session.id, self.hotkey.filter());
+                    self.insert_tab(Tab::Remote(terminal));
+                    let id = self.selected_tab;
+                    task.map(move |message| Message::RemoteTerminal { id, message })
+                }))
+            }
             Message::OpenTab => self.open_tab(),
             Message::SwitchTab(id) => {
                 // refocus tab if clicking on the already selected one
@@ -196,7 +339,42 @@ impl UI {
                 }
                 Task::none()
             }
-            Message::CloseTab(id) => self.close_tab(id),
+            Message::CloseTab(id) => {
+                // remote sessions outlive frostbyte, but not their tab
+                let session = match self.terminals.get(&id) {
+                    Some(Tab::Remote(term)) => term
+                        .session()
+                        .map(|session| (term.address().clone(), session)),
+                    _ => None,
+                };
+
+                let task = self.close_tab(id);
+
+                match session {
+                    Some((address, session)) => Task::batch([
+                        task,
+                        Task::future(async move {
+                            if let Err(err) =
+                                async_pty::remote::close_session(&address, session).await
+                            {
+                                eprintln!("Error closing session {}: {}", session, err);
+                            }
+                        })
+                        .discard(),
+                    ]),
+                    None => task,
+                }
+            }
+            Message::RefreshProcessNames => {
+                self.refresh_process_names();
+                Task::none()
+            }
+            Message::ToggleRecording(id) => {
+                if let Some(Tab::Local(term)) = self.terminals.get_mut(&id) {
+                    toggle_recording(term);
+                }
+                Task::none()
+            }
             Message::Hotkey => {
                 if self.window_id.is_some() {
                     self.close_window()
@@ -359,17 +537,39 @@ impl UI {
     }
 
     fn open_tab(&mut self) -> Task<Message> {
+        if let Some(address) = &self.session_server {
+            let (terminal, task) = RemoteTerminal::connect(address.clone(), self.hotkey.filter());
+            self.insert_tab(Tab::Remote(terminal));
+            let id = self.selected_tab;
+            return task.map(move |message| Message::RemoteTerminal { id, message });
+        }
+
+        // open the new tab in the directory of the current one
+        let cwd = match self.terminals.get(&self.selected_tab) {
+            Some(Tab::Local(term)) => term.foreground_process().and_then(|process| process.cwd),
+            _ => None,
+        };
+
+        let (terminal, task) =
+            LocalTerminal::from_pool(self.shell_pool.clone(), cwd, self.hotkey.filter());
+        self.insert_tab(Tab::Local(terminal));
+        let id = self.selected_tab;
+        task.map(move |message| Message::LocalTerminal { id, message })
+    }
+
+    /// Adds the tab and selects it.
+    fn insert_tab(&mut self, mut tab: Tab) {
         let style = frozen_term::Style::default().font(Font::with_name("RobotoMono Nerd Font"));
+        match &mut tab {
+            Tab::Local(terminal) => terminal.set_style(style),
+            Tab::Remote(terminal) => terminal.set_style(style),
+        }
 
-        let (mut local_terminal, terminal_task) = LocalTerminal::start(self.hotkey.filter());
-        local_terminal.set_style(style);
         let id = self.new_terminal_id;
         self.new_terminal_id += 1;
 
-        self.terminals.insert(id, local_terminal);
+        self.terminals.insert(id, tab);
         self.selected_tab = id;
-
-        terminal_task.map(move |message| Message::LocalTerminal { id, message })
     }
 
     fn focus_tab(&self) -> Task<Message> {
@@ -383,6 +583,7 @@ impl UI {
 
     fn close_tab(&mut self, id: u32) -> Task<Message> {
         self.terminals.remove(&id);
+        self.process_names.remove(&id);
 
         if let Some((id, _term)) = self.terminals.iter().next() {
             self.selected_tab = *id;
@@ -392,6 +593,17 @@ impl UI {
         }
     }
 
+    fn refresh_process_names(&mut self) {
+        self.process_names = self
+            .terminals
+            .iter()
+            .filter_map(|(id, tab)| match tab {
+                Tab::Local(terminal) => Some((*id, terminal.foreground_process()?.name)),
+                Tab::Remote(_) => None,
+            })
+            .collect();
+    }
+
     fn switch_tab(&mut self, id: u32) {
         if let Some(_terminal) = self.terminals.get(&id) {
             self.selected_tab = id;
@@ -402,14 +614,9 @@ impl UI {
         let selected_terminal = self.terminals.get(&self.selected_tab);
 
         let tab_view: Element<Message> = match selected_terminal {
-            Some(terminal) => {
-                container(terminal.view().map(move |message| Message::LocalTerminal {
-                    id: self.selected_tab,
-                    message,
-                }))
+            Some(terminal) => container(terminal.view(self.selected_tab))
                 .padding(10)
-                .into()
-            }
+                .into(),
             None => text("terminal closed").into(),
         };
 
@@ -419,18 +626,35 @@ impl UI {
             } else {
                 button::primary
             };
-            button(row![
-                center(text(terminal.get_title())),
+            let process_name = self.process_names.get(id).map(String::as_str);
+            let mut content = row![center(text(tab_title(terminal, process_name)))];
+            // only local shells can be recorded
+            if let Tab::Local(terminal) = terminal {
+                let record_style = if terminal.is_recording() {
+                    button::danger
+                } else {
+                    button::secondary
+                };
+                content = content.push(
+                    button(text("●").center())
+                        .on_press(Message::ToggleRecording(id.clone()))
+                        .width(30)
+                        .style(record_style),
+                );
+            }
+            content = content.push(
                 button(text("X").center())
                     .on_press(Message::CloseTab(id.clone()))
                     .width(30)
-                    .style(button::danger)
-            ])
-            .on_press(Message::SwitchTab(id.clone()))
-            .style(style)
-            .width(200)
-            .height(Length::Fill)
-            .into()
+                    .style(button::danger),
+            );
+
+            button(content)
+                .on_press(Message::SwitchTab(id.clone()))
+                .style(style)
+                .width(200)
+                .height(Length::Fill)
+                .into()
         }))
         .spacing(5);
 
@@ -474,14 +698,26 @@ impl UI {
         let selected_terminal = self.terminals.get(&self.selected_tab);
 
         match selected_terminal {
-            Some(terminal) => terminal.get_title().to_string(),
+            Some(terminal) => tab_title(
+                terminal,
+                self.process_names
+                    .get(&self.selected_tab)
+                    .map(String::as_str),
+            ),
             None => "frozen_term".to_string(),
         }
     }
 
     pub fn subscription(&self) -> Subscription<Message> {
+        let process_names = if self.window_id.is_some() {
+            iced::time::every(PROCESS_NAME_INTERVAL).map(|_| Message::RefreshProcessNames)
+        } else {
+            Subscription::none()
+        };
+
         Subscription::batch([
             Subscription::run(poll_events_sub),
+            process_names,
             keyboard::listen().filter_map(|event| {
                 if let keyboard::Event::KeyPressed {
                     key,
@@ -544,6 +780,87 @@ impl UI {
     }
 }
 
+/// Uses the title set by the terminal and falls back to the name of the foreground process.
+fn tab_title(tab: &Tab, process_name: Option<&str>) -> String {
+    match tab {
+        Tab::Local(terminal) => {
+            let title = terminal.get_title();
+            if !title.is_empty() {
+                return title.to_string();
+            }
+
+            process_name.unwrap_or_default().to_string()
+        }
+        Tab::Remote(terminal) => {
+            let title = terminal.get_title();
+            if !title.is_empty() {
+                return title.to_string();
+            }
+
+            terminal
+                .session()
+                .map(|session| format!("session {session}"))
+                .unwrap_or_default()
+        }
+    }
+}
+
+/// Recordings are written to the home directory, named after the time they were started.
+fn toggle_recording(terminal: &mut LocalTerminal) {
+    if terminal.is_recording() {
+        if let Err(err) = terminal.stop_recording() {
+            eprintln!("Error finishing recording: {}", err);
+        }
+        return;
+    }
+
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    let path = std::env::home_dir()
+        .unwrap_or_default()
+        .join(format!("frostbyte-{timestamp}.cast"));
+
+    if let Err(err) = terminal.start_recording(&path, false) {
+        eprintln!("Error starting recording at {}: {}", path.display(), err);
+    }
+}
+
+/// Opens a hyperlink clicked in a terminal with the default application of the desktop.
+/// Schemes which are passed to the opener, anything else could run programs or open local files.
+const OPENABLE_SCHEMES: [&str; 3] = ["http", "https", "mailto"];
+
+fn open_url(url: String) -> Task<Message> {
+    let scheme = url.split_once(':').map(|(scheme, _)| scheme);
+    if !scheme.is_some_and(|scheme| {
+        OPENABLE_SCHEMES
+            .iter()
+            .any(|allowed| scheme.eq_ignore_ascii_case(allowed))
+    }) {
+        eprintln!("Not opening {}: unsupported scheme", url);
+        return Task::none();
+    }
+
+    // None of the openers accept `--` to end their options (xdg-open rejects it),
+    // but an allowed url starts with its scheme and can't be mistaken for one.
+    #[cfg(target_os = "macos")]
+    let opener = "open";
+    #[cfg(target_os = "windows")]
+    let opener = "explorer";
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    let opener = "xdg-open";
+
+    // waited for on a thread, so the opener doesn't linger as a zombie
+    std::thread::spawn(move || {
+        if let Err(err) = std::process::Command::new(opener).arg(&url).status() {
+            eprintln!("Error opening {}: {}", url, err);
+        }
+    });
+
+    Task::none()
+}
+
 /// Stolen from the tauri global hotkey example for iced
 fn poll_events_sub() -> impl Stream<Item = Message> {
     channel(32, async |mut sender| {
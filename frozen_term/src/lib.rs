@@ -8,8 +8,24 @@ mod wezterm;
 #[cfg(feature = "local-terminal")]
 pub mod local_terminal;
 
+#[cfg(feature = "playback-terminal")]
+pub mod playback_terminal;
+
+#[cfg(feature = "stream-terminal")]
+pub mod stream_terminal;
+
+#[cfg(feature = "serial-terminal")]
+pub mod serial_terminal;
+
+#[cfg(feature = "telnet-terminal")]
+pub mod telnet_terminal;
+
+#[cfg(feature = "remote-terminal")]
+pub mod remote_terminal;
+
 pub use terminal::{
     Action, Message, Terminal,
+    config::TerminalConfig,
     style::{CursorShape, Palette256, Style},
 };
 pub use terminal_grid::Size;
@@ -1,60 +1,176 @@
-use std::{sync::Arc, time::Duration};
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use crate::{Style, terminal};
-use async_pty::PtyProcess;
+use crate::{Style, TerminalConfig, terminal};
+pub use async_pty::{
+    Command, Error, ExitStatus, MemoryPty, MemoryPtyController, OutputBroadcast, PASTE_CHUNK_LEN,
+    Paste, PasteHandle, ProcessInfo, PtyBackend, PtyProcess, Recorder, ShellPool, Signal,
+    TerminalSize,
+};
 use iced::{
     self, Element, Length, Task,
-    task::sipper,
-    widget::{center, text},
+    alignment::{Horizontal, Vertical},
+    task::{Handle, sipper},
+    widget::{button, center, column, container, progress_bar, row, stack, text},
 };
 
-#[derive(Debug, Clone)]
-pub struct Message(InnerMessage);
+#[derive(Debug)]
+pub struct Message<B: PtyBackend = PtyProcess>(InnerMessage<B>);
 
-#[derive(Debug, Clone)]
-enum InnerMessage {
-    Opened(Arc<(PtyProcess, tokio::sync::mpsc::Receiver<Vec<u8>>)>),
+#[derive(Debug)]
+enum InnerMessage<B: PtyBackend> {
+    Opened(Arc<(B, tokio::sync::mpsc::Receiver<Vec<u8>>)>),
+    Failed(Arc<Error>),
+    Retry,
     Terminal(terminal::Message),
-    Output(Vec<u8>),
+    // tagged with the generation of the backend, so messages of a replaced one are ignored
+    Output(usize, Vec<u8>),
+    OutputClosed(usize),
     InjectInput(Vec<u8>),
-    Closed,
+    Closed(usize, Option<ExitStatus>),
+    PasteProgress,
+    PasteFinished,
+    CancelPaste,
+}
+
+// derive(Clone) would require the backend itself to be Clone
+impl<B: PtyBackend> Clone for Message<B> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<B: PtyBackend> Clone for InnerMessage<B> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Opened(opened) => Self::Opened(opened.clone()),
+            Self::Failed(err) => Self::Failed(err.clone()),
+            Self::Retry => Self::Retry,
+            Self::Terminal(message) => Self::Terminal(message.clone()),
+            Self::Output(generation, output) => Self::Output(*generation, output.clone()),
+            Self::OutputClosed(generation) => Self::OutputClosed(*generation),
+            Self::InjectInput(input) => Self::InjectInput(input.clone()),
+            Self::Closed(generation, status) => Self::Closed(*generation, status.clone()),
+            Self::PasteProgress => Self::PasteProgress,
+            Self::PasteFinished => Self::PasteFinished,
+            Self::CancelPaste => Self::CancelPaste,
+        }
+    }
 }
 
-pub enum Action {
-    Run(Task<Message>),
+pub enum Action<B: PtyBackend = PtyProcess> {
+    Run(Task<Message<B>>),
     IdChanged,
-    Close,
+    /// A hyperlink in the output was Ctrl+clicked.
+    OpenUrl(String),
+    /// The process has exited.
+    /// `status` is `None` if the exit status could not be determined.
+    Close {
+        status: Option<ExitStatus>,
+    },
     None,
 }
 
-enum State {
+enum State<B> {
     Starting,
-    Active(PtyProcess),
-    Closed,
+    // shared with tasks which wait for space in the input channel
+    Active(Arc<B>),
+    Closed(Option<ExitStatus>),
+    Failed(Arc<Error>),
 }
 
-pub struct LocalTerminal {
-    state: State,
+/// A terminal connected to a process in a pty.
+///
+/// By default this is a native [`PtyProcess`], but any [`PtyBackend`] can be used,
+/// e.g. a [`MemoryPty`] to test how your application reacts to a terminal.
+pub struct LocalTerminal<B: PtyBackend = PtyProcess> {
+    state: State<B>,
     display: terminal::Terminal,
+    // used to retry after a failure, only available if the terminal started the backend itself
+    reopen: Option<Box<dyn Fn() -> Task<Message<B>>>>,
+    // last size sent to the backend, needed for the header of new recordings
+    size: TerminalSize,
+    recording: Option<Recording>,
+    // the error which stopped the last recording, until it is taken
+    recording_error: Option<io::Error>,
+    paste: Option<Pasting>,
+    // increased whenever a backend is attached, e.g. after retrying
+    generation: usize,
+}
+
+struct Recording {
+    recorder: Recorder,
+    include_input: bool,
+}
+
+/// Progress is only shown for pastes of at least this size.
+const PASTE_PROGRESS_MIN_LEN: usize = 256 * 1024;
+const PASTE_PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+struct Pasting {
+    handle: PasteHandle,
+    // input which arrived during the paste, it is written afterwards to keep the order
+    queued: Vec<u8>,
+    _task: Handle,
 }
 
-impl LocalTerminal {
+impl LocalTerminal<PtyProcess> {
+    /// Starts the default shell of the current user.
     pub fn start(
         key_filter: impl 'static + Fn(&iced::keyboard::Key, &iced::keyboard::Modifiers) -> bool,
     ) -> (Self, Task<Message>) {
-        let size = async_pty::TerminalSize { cols: 80, rows: 24 };
+        Self::spawn(Command::shell(), key_filter)
+    }
+
+    /// Starts the given command instead of the default shell.
+    pub fn spawn(
+        command: Command,
+        key_filter: impl 'static + Fn(&iced::keyboard::Key, &iced::keyboard::Modifiers) -> bool,
+    ) -> (Self, Task<Message>) {
+        Self::with_opener(move || Self::open(command.clone()), key_filter)
+    }
+
+    /// Takes a shell from the pool, which is usually ready right away.
+    ///
+    /// `cwd` is passed on to [`ShellPool::take`].
+    pub fn from_pool(
+        pool: ShellPool,
+        cwd: Option<PathBuf>,
+        key_filter: impl 'static + Fn(&iced::keyboard::Key, &iced::keyboard::Modifiers) -> bool,
+    ) -> (Self, Task<Message>) {
+        Self::with_opener(
+            move || {
+                let pool = pool.clone();
+                let cwd = cwd.clone();
+                Self::open_with(async move { pool.take(cwd.as_deref()).await })
+            },
+            key_filter,
+        )
+    }
+
+    fn with_opener(
+        open: impl 'static + Fn() -> Task<Message>,
+        key_filter: impl 'static + Fn(&iced::keyboard::Key, &iced::keyboard::Modifiers) -> bool,
+    ) -> (Self, Task<Message>) {
         let (display, display_task) = terminal::Terminal::new();
         let display = display.key_filter(key_filter);
 
-        let start_task = Task::future(async {
-            let (process, output) = PtyProcess::shell(size).await.unwrap();
-            Message(InnerMessage::Opened(Arc::new((process, output))))
-        });
+        let start_task = open();
 
         (
             Self {
                 state: State::Starting,
                 display,
+                reopen: Some(Box::new(open)),
+                size: TerminalSize::default(),
+                recording: None,
+                recording_error: None,
+                paste: None,
+                generation: 0,
             },
             Task::batch([
                 display_task.map(InnerMessage::Terminal).map(Message),
@@ -63,6 +179,80 @@ impl LocalTerminal {
         )
     }
 
+    fn open(command: Command) -> Task<Message> {
+        Self::open_with(PtyProcess::spawn(command))
+    }
+
+    fn open_with(
+        open: impl Future<
+            Output = async_pty::Result<(PtyProcess, tokio::sync::mpsc::Receiver<Vec<u8>>)>,
+        > + Send
+        + 'static,
+    ) -> Task<Message> {
+        Task::future(async move {
+            match open.await {
+                Ok((process, output)) => InnerMessage::Opened(Arc::new((process, output))),
+                Err(err) => InnerMessage::Failed(Arc::new(err)),
+            }
+        })
+        .map(Message)
+    }
+}
+
+impl<B: PtyBackend> LocalTerminal<B> {
+    /// Connects the terminal to an already running backend and its output.
+    pub fn with_backend(
+        backend: B,
+        output: tokio::sync::mpsc::Receiver<Vec<u8>>,
+        key_filter: impl 'static + Fn(&iced::keyboard::Key, &iced::keyboard::Modifiers) -> bool,
+    ) -> (Self, Task<Message<B>>) {
+        let (display, display_task) = terminal::Terminal::new();
+        let display = display.key_filter(key_filter);
+
+        let mut terminal = Self {
+            state: State::Starting,
+            display,
+            reopen: None,
+            size: TerminalSize::default(),
+            recording: None,
+            recording_error: None,
+            paste: None,
+            generation: 0,
+        };
+        let output_task = terminal.attach(backend, output);
+
+        (
+            terminal,
+            Task::batch([
+                display_task.map(InnerMessage::Terminal).map(Message),
+                output_task,
+            ]),
+        )
+    }
+
+    fn attach(
+        &mut self,
+        backend: B,
+        output: tokio::sync::mpsc::Receiver<Vec<u8>>,
+    ) -> Task<Message<B>> {
+        self.generation += 1;
+        let generation = self.generation;
+
+        let stream = sipper(move |mut sender| async move {
+            let mut output = output;
+            while let Some(chunk) = output.recv().await {
+                sender.send(InnerMessage::Output(generation, chunk)).await;
+            }
+
+            sender.send(InnerMessage::OutputClosed(generation)).await;
+        });
+
+        self.state = State::Active(Arc::new(backend));
+        self.paste = None;
+
+        Task::stream(stream).map(Message)
+    }
+
     pub fn style(mut self, style: Style) -> Self {
         self.set_style(style);
         self
@@ -72,27 +262,37 @@ impl LocalTerminal {
         self.display.set_style(style);
     }
 
+    pub fn terminal_config(mut self, config: TerminalConfig) -> Self {
+        self.set_terminal_config(config);
+        self
+    }
+
+    pub fn set_terminal_config(&mut self, config: TerminalConfig) {
+        self.display.set_terminal_config(config);
+    }
+
     #[must_use]
-    pub fn update(&mut self, message: Message) -> Action {
+    pub fn update(&mut self, message: Message<B>) -> Action<B> {
         match message.0 {
             InnerMessage::Opened(arc) => {
                 let (process, output) = Arc::into_inner(arc).unwrap();
+                // started with a default size, the widget might already know the real one
+                let _ = process.try_resize(self.size.clone());
 
-                let stream = sipper(|mut sender| async move {
-                    let mut output = output;
-                    while let Some(chunk) = output.recv().await {
-                        sender.send(InnerMessage::Output(chunk)).await;
-                    }
-
-                    sender.send(InnerMessage::Closed).await;
-                });
-
-                let task = Task::stream(stream).map(Message);
-
-                self.state = State::Active(process);
-
-                Action::Run(task)
+                Action::Run(self.attach(process, output))
+            }
+            InnerMessage::Failed(err) => {
+                self.state = State::Failed(err);
+                self.paste = None;
+                Action::None
             }
+            InnerMessage::Retry => match &self.reopen {
+                Some(reopen) => {
+                    self.state = State::Starting;
+                    Action::Run(reopen())
+                }
+                None => Action::None,
+            },
             InnerMessage::Terminal(message) => {
                 let action = self.display.update(message);
 
@@ -102,48 +302,287 @@ impl LocalTerminal {
                         Action::Run(task.map(InnerMessage::Terminal).map(Message))
                     }
                     terminal::Action::IdChanged => Action::IdChanged,
-                    terminal::Action::Input(input) => {
-                        if let State::Active(pty) = &self.state {
-                            pty.try_write(input).unwrap();
-                        }
-                        Action::None
-                    }
-                    terminal::Action::Resize(size) => {
-                        if let State::Active(pty) = &self.state {
-                            pty.try_resize(async_pty::TerminalSize {
-                                rows: size.rows as u16,
-                                cols: size.cols as u16,
-                            })
-                            .unwrap();
-                        }
-                        Action::None
-                    }
+                    terminal::Action::OpenUrl(url) => Action::OpenUrl(url),
+                    terminal::Action::Input(input) => self.write(input),
+                    terminal::Action::Resize(size) => self.resize(TerminalSize {
+                        rows: size.rows as u16,
+                        cols: size.cols as u16,
+                        pixel_width: size.pixel_width as u16,
+                        pixel_height: size.pixel_height as u16,
+                    }),
                 }
             }
-            InnerMessage::InjectInput(input) => {
-                if let State::Active(pty) = &self.state {
-                    pty.try_write(input).unwrap();
-                }
+            InnerMessage::InjectInput(input) => self.write(input),
+            // only here to redraw the progress
+            InnerMessage::PasteProgress => Action::None,
+            InnerMessage::PasteFinished => match self.paste.take() {
+                Some(paste) if !paste.queued.is_empty() => self.send(paste.queued),
+                _ => Action::None,
+            },
+            InnerMessage::CancelPaste => {
+                self.cancel_paste();
+                Action::None
+            }
+            InnerMessage::Output(generation, _) | InnerMessage::OutputClosed(generation)
+                if generation != self.generation =>
+            {
                 Action::None
             }
-            InnerMessage::Output(output) => {
+            InnerMessage::Closed(generation, _) if generation != self.generation => Action::None,
+            InnerMessage::Output(_, output) => {
+                self.record(|recorder| recorder.output(&output));
                 self.display.advance_bytes(output);
 
                 Action::None
             }
-            InnerMessage::Closed => {
-                self.state = State::Closed;
+            InnerMessage::OutputClosed(generation) => {
+                let State::Active(pty) = &self.state else {
+                    return Action::None;
+                };
+
+                if let Some(err) = pty.take_error() {
+                    self.state = State::Failed(Arc::new(err));
+                    return Action::None;
+                }
+
+                let exited = pty.exited();
+                Action::Run(
+                    Task::future(exited)
+                        .map(move |status| InnerMessage::Closed(generation, status))
+                        .map(Message),
+                )
+            }
+            InnerMessage::Closed(_, status) => {
+                self.state = State::Closed(status.clone());
+
+                Action::Close { status }
+            }
+        }
+    }
+
+    fn write(&mut self, input: Vec<u8>) -> Action<B> {
+        if self
+            .recording
+            .as_ref()
+            .is_some_and(|recording| recording.include_input)
+        {
+            self.record(|recorder| recorder.input(&input));
+        }
+
+        self.send(input)
+    }
+
+    fn send(&mut self, input: Vec<u8>) -> Action<B> {
+        let State::Active(pty) = &self.state else {
+            return Action::None;
+        };
+
+        if let Some(paste) = &mut self.paste {
+            paste.queued.extend(input);
+            return Action::None;
+        }
+
+        if input.len() <= PASTE_CHUNK_LEN {
+            match pty.try_write(input.clone()) {
+                Ok(()) => return Action::None,
+                // wait for the writer to catch up instead of dropping the input
+                Err(Error::ChannelFull) => {}
+                Err(err) => {
+                    self.state = State::Failed(Arc::new(err));
+                    return Action::None;
+                }
+            }
+        }
+
+        Action::Run(self.paste(pty.clone(), input))
+    }
+
+    /// Writes large or delayed input in chunks, further input is queued until it is done.
+    fn paste(&mut self, pty: Arc<B>, input: Vec<u8>) -> Task<Message<B>> {
+        let mut paste = Paste::new(input);
+        let handle = paste.handle();
+
+        let stream = sipper(|mut sender| async move {
+            let mut reported = Instant::now();
+            loop {
+                match paste.write_next(&*pty).await {
+                    Ok(true) => {
+                        if reported.elapsed() >= PASTE_PROGRESS_INTERVAL {
+                            reported = Instant::now();
+                            sender.send(InnerMessage::PasteProgress).await;
+                        }
+                    }
+                    Ok(false) => break,
+                    Err(err) => {
+                        sender.send(InnerMessage::Failed(Arc::new(err))).await;
+                        return;
+                    }
+                }
+            }
+
+            sender.send(InnerMessage::PasteFinished).await;
+        });
+
+        let (task, abort) = Task::stream(stream).abortable();
+        self.paste = Some(Pasting {
+            handle,
+            queued: Vec::new(),
+            _task: abort.abort_on_drop(),
+        });
+
+        task.map(Message)
+    }
+
+    /// Stops the running paste, input typed in the meantime is still written.
+    pub fn cancel_paste(&mut self) {
+        if let Some(paste) = &self.paste {
+            paste.handle.cancel();
+        }
+    }
+
+    /// Returns the progress of the running paste.
+    pub fn paste_progress(&self) -> Option<&PasteHandle> {
+        self.paste.as_ref().map(|paste| &paste.handle)
+    }
+
+    fn resize(&mut self, size: TerminalSize) -> Action<B> {
+        self.record(|recorder| recorder.resize(&size));
+        self.size = size.clone();
 
-                Action::Close
+        let State::Active(pty) = &self.state else {
+            return Action::None;
+        };
+
+        match pty.try_resize(size.clone()) {
+            Ok(()) => Action::None,
+            Err(Error::ChannelFull) => {
+                let pty = pty.clone();
+                Self::send_later(async move { pty.resize(size).await })
+            }
+            Err(err) => {
+                self.state = State::Failed(Arc::new(err));
+                Action::None
             }
         }
     }
 
-    pub fn view<'a>(&'a self) -> Element<'a, Message> {
+    fn send_later(send: impl Future<Output = async_pty::Result<()>> + Send + 'static) -> Action<B> {
+        let task = Task::future(async move { send.await.err().map(Arc::new) })
+            .and_then(|err| Task::done(Message(InnerMessage::Failed(err))));
+
+        Action::Run(task)
+    }
+
+    pub fn view<'a>(&'a self) -> Element<'a, Message<B>> {
         match &self.state {
             State::Starting => center(text!("opening pty...")).into(),
-            State::Active(_) => self.display.view().map(InnerMessage::Terminal).map(Message),
-            State::Closed => center(text!("pty closed")).height(Length::Fill).into(),
+            State::Active(_) => {
+                let terminal = self.display.view().map(InnerMessage::Terminal).map(Message);
+
+                match &self.paste {
+                    Some(paste) if paste.handle.len() >= PASTE_PROGRESS_MIN_LEN => {
+                        stack![terminal, paste_overlay(&paste.handle)].into()
+                    }
+                    _ => terminal,
+                }
+            }
+            State::Closed(status) => center(text(exit_message(status.as_ref())))
+                .height(Length::Fill)
+                .into(),
+            State::Failed(err) => {
+                let mut content = column![text(err.to_string())]
+                    .spacing(10)
+                    .align_x(Horizontal::Center);
+
+                if self.reopen.is_some() {
+                    content =
+                        content.push(button(text("Retry")).on_press(Message(InnerMessage::Retry)));
+                }
+
+                center(content).height(Length::Fill).into()
+            }
+        }
+    }
+
+    /// Starts recording the session as an asciicast v2 file at `path`, replacing an existing file.
+    ///
+    /// Input is only recorded if `include_input` is set, as it may contain passwords.
+    /// A running recording is stopped first.
+    pub fn start_recording(
+        &mut self,
+        path: impl AsRef<Path>,
+        include_input: bool,
+    ) -> io::Result<()> {
+        self.stop_recording()?;
+
+        let env = match &self.state {
+            State::Active(pty) => pty.recording_env(),
+            _ => Vec::new(),
+        };
+        self.recording = Some(Recording {
+            recorder: Recorder::create(path, &self.size, env)?,
+            include_input,
+        });
+
+        Ok(())
+    }
+
+    pub fn stop_recording(&mut self) -> io::Result<()> {
+        match self.recording.take() {
+            Some(recording) => recording.recorder.finish().map(|_| ()),
+            None => Ok(()),
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Takes the error which stopped the last recording, if there was one.
+    ///
+    /// A recording which fails to write is stopped, the terminal itself keeps working.
+    pub fn take_recording_error(&mut self) -> Option<io::Error> {
+        self.recording_error.take()
+    }
+
+    fn record(&mut self, event: impl FnOnce(&mut Recorder) -> io::Result<()>) {
+        if let Some(recording) = &mut self.recording
+            && let Err(err) = event(&mut recording.recorder)
+        {
+            self.recording = None;
+            self.recording_error = Some(err);
+        }
+    }
+
+    /// Returns the error which stopped the terminal, if there was one.
+    pub fn error(&self) -> Option<&Error> {
+        match &self.state {
+            State::Failed(err) => Some(err),
+            _ => None,
+        }
+    }
+
+    /// Sends a signal to the foreground process of the terminal.
+    pub fn signal(&self, signal: Signal) -> async_pty::Result<()> {
+        match &self.state {
+            State::Active(pty) => pty.signal(signal),
+            _ => Err(Error::ChildGone),
+        }
+    }
+
+    /// Forcefully terminates the process started by the terminal.
+    pub fn kill(&self) -> async_pty::Result<()> {
+        match &self.state {
+            State::Active(pty) => pty.kill(),
+            _ => Err(Error::ChildGone),
+        }
+    }
+
+    /// Returns the exit status once the process has exited.
+    pub fn exit_status(&self) -> Option<&ExitStatus> {
+        match &self.state {
+            State::Closed(status) => status.as_ref(),
+            _ => None,
         }
     }
 
@@ -151,6 +590,16 @@ impl LocalTerminal {
         self.display.get_title()
     }
 
+    /// Returns the process currently running in the foreground of the terminal.
+    ///
+    /// This is useful as a fallback title, as many shells don't set one.
+    pub fn foreground_process(&self) -> Option<ProcessInfo> {
+        match &self.state {
+            State::Active(pty) => pty.foreground_process(),
+            _ => None,
+        }
+    }
+
     #[must_use]
     pub fn focus<T>(&self) -> Task<T>
     where
@@ -166,30 +615,28 @@ impl LocalTerminal {
     /// When writing commands manually, you'll need to ensure that they are not influenced by what the user has typed
     /// and you will also have to handle key encoding and control characters yourself.
     #[must_use]
-    pub fn inject_input(&self, input: InputSequence) -> Task<Message> {
-        if let State::Active(ref pty) = self.state {
+    pub fn inject_input(&self, input: InputSequence) -> Task<Message<B>> {
+        if let State::Active(_) = self.state {
+            // written like typed input, which waits for the pty instead of dropping input
+            let inject = |input: Vec<u8>| Task::done(Message(InnerMessage::InjectInput(input)));
+
             match input {
-                InputSequence::Raw(input) => {
-                    let _ = pty.try_write(input);
-                    Task::none()
-                }
+                InputSequence::Raw(input) => inject(input),
                 InputSequence::AbortAndRaw(input) => {
-                    let _ = pty.try_write(b"\x03".to_vec());
                     // While I'd love to skip this weird helper task, my shell just doesn't clear the current line without it.
                     //
-                    Task::future(async move {
+                    inject(b"\x03".to_vec()).chain(Task::future(async move {
                         tokio::time::sleep(INJECTION_DELAY).await;
                         Message(InnerMessage::InjectInput(input))
-                    })
+                    }))
                 }
                 InputSequence::AbortAndCommand(mut input) => {
-                    let _ = pty.try_write(b"\x03".to_vec());
                     input.push('\n');
                     let input = input.into_bytes();
-                    Task::future(async move {
+                    inject(b"\x03".to_vec()).chain(Task::future(async move {
                         tokio::time::sleep(INJECTION_DELAY).await;
                         Message(InnerMessage::InjectInput(input))
-                    })
+                    }))
                 }
             }
         } else {
@@ -200,6 +647,61 @@ impl LocalTerminal {
 
 const INJECTION_DELAY: Duration = Duration::from_millis(100);
 
+// shown on top of the terminal, as shrinking it would resize the pty during the paste
+fn paste_overlay<'a, B: PtyBackend>(handle: &PasteHandle) -> Element<'a, Message<B>> {
+    let status = text!(
+        "pasting {} of {} KiB",
+        handle.written() / 1024,
+        handle.len() / 1024
+    )
+    .size(14);
+
+    let cancel = if handle.is_cancelled() {
+        button(text("Cancel").size(14)).padding([4, 8])
+    } else {
+        button(text("Cancel").size(14))
+            .padding([4, 8])
+            .on_press(Message(InnerMessage::CancelPaste))
+    };
+
+    let panel = container(
+        row![status, progress_bar(0.0..=1.0, handle.progress()), cancel]
+            .spacing(10)
+            .align_y(Vertical::Center),
+    )
+    .style(|_theme| container::Style {
+        background: Some(iced::Background::Color(iced::Color::from_rgb(
+            0.2, 0.2, 0.2,
+        ))),
+        border: iced::Border {
+            color: iced::Color::from_rgb(0.5, 0.5, 0.5),
+            width: 1.0,
+            radius: 4.0.into(),
+        },
+        ..Default::default()
+    })
+    .padding(8)
+    .width(400);
+
+    container(panel)
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .align_x(Horizontal::Center)
+        .align_y(Vertical::Bottom)
+        .padding(10)
+        .into()
+}
+
+fn exit_message(status: Option<&ExitStatus>) -> String {
+    match status {
+        Some(status) => match status.signal() {
+            Some(signal) => format!("process terminated by {signal}"),
+            None => format!("process exited with code {}", status.exit_code()),
+        },
+        None => "pty closed".to_string(),
+    }
+}
+
 pub enum InputSequence {
     /// !!!WARNING!!!
     ///
@@ -215,3 +717,153 @@ pub enum InputSequence {
     /// Be aware that your command will not be sanitized!.
     AbortAndCommand(String),
 }
+
+#[cfg(test)]
+mod tests {
+    use async_pty::TerminalInput;
+
+    use super::*;
+
+    fn memory_terminal() -> (LocalTerminal<MemoryPty>, MemoryPtyController) {
+        let (pty, output, controller) = MemoryPty::new();
+        let (terminal, _task) = LocalTerminal::with_backend(pty, output, |_, _| false);
+        (terminal, controller)
+    }
+
+    fn update(
+        terminal: &mut LocalTerminal<MemoryPty>,
+        message: InnerMessage<MemoryPty>,
+    ) -> Action<MemoryPty> {
+        terminal.update(Message(message))
+    }
+
+    #[test]
+    fn output_reaches_the_display() {
+        let (mut terminal, _controller) = memory_terminal();
+        let generation = terminal.generation;
+
+        let action = update(
+            &mut terminal,
+            InnerMessage::Output(generation, b"\x1b]0;memory\x07".to_vec()),
+        );
+        assert!(matches!(action, Action::None));
+        assert_eq!(terminal.get_title(), "memory");
+
+        // output of a backend replaced by retrying
+        let _ = update(
+            &mut terminal,
+            InnerMessage::Output(generation - 1, b"\x1b]0;old\x07".to_vec()),
+        );
+        assert_eq!(terminal.get_title(), "memory");
+    }
+
+    #[test]
+    fn typed_input_reaches_the_pty() {
+        let (mut terminal, mut controller) = memory_terminal();
+
+        let action = update(
+            &mut terminal,
+            InnerMessage::Terminal(terminal::Message::input(b"ls\r")),
+        );
+        assert!(matches!(action, Action::None));
+        let Some(TerminalInput::Input(input)) = controller.try_recv() else {
+            panic!("expected input");
+        };
+        assert_eq!(input, b"ls\r");
+
+        let action = update(&mut terminal, InnerMessage::InjectInput(b"pwd\r".to_vec()));
+        assert!(matches!(action, Action::None));
+        let Some(TerminalInput::Input(input)) = controller.try_recv() else {
+            panic!("expected input");
+        };
+        assert_eq!(input, b"pwd\r");
+    }
+
+    #[test]
+    fn input_waits_for_a_full_pty() {
+        let (mut terminal, _controller) = memory_terminal();
+
+        loop {
+            let action = update(
+                &mut terminal,
+                InnerMessage::Terminal(terminal::Message::input(b"x")),
+            );
+            if let Action::Run(_) = action {
+                break;
+            }
+        }
+
+        // the waiting input keeps its order with what is typed next
+        assert!(terminal.paste_progress().is_some());
+        assert!(terminal.error().is_none());
+    }
+
+    #[test]
+    fn resize_is_forwarded() {
+        let (mut terminal, mut controller) = memory_terminal();
+
+        let size = crate::Size {
+            cols: 100,
+            rows: 20,
+            pixel_width: 800,
+            pixel_height: 400,
+        };
+        let action = update(
+            &mut terminal,
+            InnerMessage::Terminal(terminal::Message::resize(size)),
+        );
+        assert!(matches!(action, Action::None));
+
+        let Some(TerminalInput::Resize(size)) = controller.try_recv() else {
+            panic!("expected resize");
+        };
+        assert_eq!(
+            (size.cols, size.rows, size.pixel_width, size.pixel_height),
+            (100, 20, 800, 400)
+        );
+    }
+
+    #[tokio::test]
+    async fn exit_closes_the_terminal() {
+        let (mut terminal, mut controller) = memory_terminal();
+        let generation = terminal.generation;
+
+        controller.exit(ExitStatus::with_exit_code(3));
+
+        let Action::Run(_) = update(&mut terminal, InnerMessage::OutputClosed(generation)) else {
+            panic!("expected to wait for the exit status");
+        };
+        let State::Active(pty) = &terminal.state else {
+            panic!("expected the backend to be active until it exited");
+        };
+        let status = pty.exited().await;
+
+        let Action::Close { status } =
+            update(&mut terminal, InnerMessage::Closed(generation, status))
+        else {
+            panic!("expected the terminal to close");
+        };
+        assert_eq!(status.unwrap().exit_code(), 3);
+        assert_eq!(
+            exit_message(terminal.exit_status()),
+            "process exited with code 3"
+        );
+        assert!(matches!(
+            terminal.signal(Signal::Interrupt),
+            Err(Error::ChildGone)
+        ));
+    }
+
+    #[test]
+    fn failure_shows_the_error() {
+        let (mut terminal, mut controller) = memory_terminal();
+        let generation = terminal.generation;
+
+        controller.fail(Error::Read(io::ErrorKind::BrokenPipe.into()));
+
+        let action = update(&mut terminal, InnerMessage::OutputClosed(generation));
+        assert!(matches!(action, Action::None));
+        assert!(matches!(terminal.error(), Some(Error::Read(_))));
+        assert_eq!(exit_message(None), "pty closed");
+    }
+}
@@ -0,0 +1,461 @@
+use std::{
+    io,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::{Size, Style, TerminalConfig, terminal};
+pub use async_pty::{Cast, CastEvent, CastEventKind};
+use iced::{
+    Element, Length, Task,
+    alignment::Vertical,
+    widget::{button, center, column, row, slider, text},
+};
+
+const SPEEDS: [f32; 4] = [0.5, 1.0, 2.0, 4.0];
+
+#[derive(Debug, Clone)]
+pub struct Message(InnerMessage);
+
+#[derive(Debug, Clone)]
+enum InnerMessage {
+    Loaded(Arc<Cast>),
+    Failed(Arc<io::Error>),
+    Terminal(terminal::Message),
+    Tick(u64),
+    TogglePause,
+    Speed(f32),
+    Seek(f32),
+}
+
+pub enum Action {
+    Run(Task<Message>),
+    IdChanged,
+    /// A hyperlink in the output was Ctrl+clicked.
+    OpenUrl(String),
+    /// The end of the recording was reached.
+    Finished,
+    None,
+}
+
+enum State {
+    Loading,
+    Ready(Playback),
+    Failed(Arc<io::Error>),
+}
+
+struct Playback {
+    cast: Arc<Cast>,
+    /// index of the first event which hasn't been shown yet
+    next_event: usize,
+    /// position in the recording at the time playback was last resumed or paused
+    position: Duration,
+    /// `None` while paused
+    resumed: Option<Instant>,
+}
+
+/// Replays an asciicast v2 recording, e.g. one created by [`LocalTerminal::start_recording`](crate::local_terminal::LocalTerminal::start_recording).
+///
+/// The terminal keeps the size of the recording instead of adapting to the widget.
+pub struct PlaybackTerminal {
+    state: State,
+    display: terminal::Terminal,
+    style: Style,
+    terminal_config: TerminalConfig,
+    speed: f32,
+    // scheduled ticks from before the last pause, seek or speed change are ignored
+    generation: u64,
+}
+
+impl PlaybackTerminal {
+    /// Loads the recording at `path` and starts playing it.
+    pub fn open(path: impl Into<PathBuf>) -> (Self, Task<Message>) {
+        let path = path.into();
+        let (terminal, display_task) = Self::new();
+
+        let load_task = Task::future(async move {
+            let content = match tokio::fs::read_to_string(path).await {
+                Ok(content) => content,
+                Err(err) => return InnerMessage::Failed(Arc::new(err)),
+            };
+
+            match Cast::parse(&content) {
+                Ok(cast) => InnerMessage::Loaded(Arc::new(cast)),
+                Err(err) => InnerMessage::Failed(Arc::new(err)),
+            }
+        })
+        .map(Message);
+
+        (terminal, Task::batch([display_task, load_task]))
+    }
+
+    /// Starts playing an already parsed recording.
+    pub fn from_cast(cast: Cast) -> (Self, Task<Message>) {
+        let (terminal, display_task) = Self::new();
+
+        (
+            terminal,
+            Task::batch([
+                display_task,
+                Task::done(Message(InnerMessage::Loaded(Arc::new(cast)))),
+            ]),
+        )
+    }
+
+    fn new() -> (Self, Task<Message>) {
+        let (display, display_task) = terminal::Terminal::new();
+
+        (
+            Self {
+                state: State::Loading,
+                display: display.fixed_size(true),
+                style: Style::default(),
+                terminal_config: TerminalConfig::default(),
+                speed: 1.0,
+                generation: 0,
+            },
+            display_task.map(InnerMessage::Terminal).map(Message),
+        )
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.set_style(style);
+        self
+    }
+
+    pub fn set_style(&mut self, style: Style) {
+        self.style = style.clone();
+        self.display.set_style(style);
+    }
+
+    pub fn terminal_config(mut self, config: TerminalConfig) -> Self {
+        self.set_terminal_config(config);
+        self
+    }
+
+    pub fn set_terminal_config(&mut self, config: TerminalConfig) {
+        self.terminal_config = config.clone();
+        self.display.set_terminal_config(config);
+    }
+
+    #[must_use]
+    pub fn update(&mut self, message: Message) -> Action {
+        match message.0 {
+            InnerMessage::Loaded(cast) => {
+                let reset_task = self.reset(&cast);
+                self.state = State::Ready(Playback {
+                    cast,
+                    next_event: 0,
+                    position: Duration::ZERO,
+                    resumed: None,
+                });
+
+                Action::Run(Task::batch([reset_task, self.play()]))
+            }
+            InnerMessage::Failed(err) => {
+                self.state = State::Failed(err);
+                Action::None
+            }
+            InnerMessage::Terminal(message) => match self.display.update(message) {
+                terminal::Action::Run(task) => {
+                    Action::Run(task.map(InnerMessage::Terminal).map(Message))
+                }
+                terminal::Action::IdChanged => Action::IdChanged,
+                terminal::Action::OpenUrl(url) => Action::OpenUrl(url),
+                // there is no process to send input to
+                terminal::Action::Input(_) => Action::None,
+                terminal::Action::Resize(_) => Action::None,
+                terminal::Action::None => Action::None,
+            },
+            InnerMessage::Tick(generation) => {
+                if generation != self.generation {
+                    return Action::None;
+                }
+
+                self.advance();
+
+                let State::Ready(playback) = &self.state else {
+                    return Action::None;
+                };
+
+                if playback.next_event < playback.cast.events.len() {
+                    Action::Run(self.schedule())
+                } else {
+                    self.pause();
+                    Action::Finished
+                }
+            }
+            InnerMessage::TogglePause => {
+                if self.is_paused() {
+                    Action::Run(self.play())
+                } else {
+                    self.pause();
+                    Action::None
+                }
+            }
+            InnerMessage::Speed(speed) => Action::Run(self.set_speed(speed)),
+            InnerMessage::Seek(seconds) => Action::Run(self.seek(Duration::from_secs_f32(seconds))),
+        }
+    }
+
+    /// Resumes playback, starting over if the end was already reached.
+    #[must_use]
+    pub fn play(&mut self) -> Task<Message> {
+        let State::Ready(playback) = &mut self.state else {
+            return Task::none();
+        };
+
+        if playback.resumed.is_some() {
+            return Task::none();
+        }
+
+        let restart_task = if playback.next_event >= playback.cast.events.len() {
+            self.seek(Duration::ZERO)
+        } else {
+            Task::none()
+        };
+
+        if let State::Ready(playback) = &mut self.state {
+            playback.resumed = Some(Instant::now());
+        }
+        self.generation += 1;
+
+        Task::batch([restart_task, self.schedule()])
+    }
+
+    pub fn pause(&mut self) {
+        let position = self.position();
+        if let State::Ready(playback) = &mut self.state {
+            playback.position = position;
+            playback.resumed = None;
+        }
+        self.generation += 1;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        match &self.state {
+            State::Ready(playback) => playback.resumed.is_none(),
+            _ => true,
+        }
+    }
+
+    /// Sets the playback speed, e.g. `2.0` to play twice as fast as recorded.
+    #[must_use]
+    pub fn set_speed(&mut self, speed: f32) -> Task<Message> {
+        let position = self.position();
+        self.speed = speed.max(f32::EPSILON);
+        self.generation += 1;
+
+        let State::Ready(playback) = &mut self.state else {
+            return Task::none();
+        };
+
+        playback.position = position;
+        if playback.resumed.is_some() {
+            playback.resumed = Some(Instant::now());
+        }
+
+        self.schedule()
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    /// Jumps to the given position in the recording.
+    ///
+    /// Seeking backwards re-feeds the recording from the start into a fresh terminal.
+    #[must_use]
+    pub fn seek(&mut self, position: Duration) -> Task<Message> {
+        let State::Ready(playback) = &self.state else {
+            return Task::none();
+        };
+
+        let position = position.min(playback.cast.duration());
+        let reset_task = if position < self.position() {
+            let cast = playback.cast.clone();
+            let task = self.reset(&cast);
+            if let State::Ready(playback) = &mut self.state {
+                playback.next_event = 0;
+            }
+            task
+        } else {
+            Task::none()
+        };
+
+        if let State::Ready(playback) = &mut self.state {
+            playback.position = position;
+            if playback.resumed.is_some() {
+                playback.resumed = Some(Instant::now());
+            }
+        }
+        self.generation += 1;
+
+        self.advance();
+
+        Task::batch([reset_task, self.schedule()])
+    }
+
+    /// The current position in the recording.
+    pub fn position(&self) -> Duration {
+        match &self.state {
+            State::Ready(playback) => {
+                let played = playback
+                    .resumed
+                    .map(|resumed| resumed.elapsed().mul_f32(self.speed))
+                    .unwrap_or_default();
+
+                (playback.position + played).min(playback.cast.duration())
+            }
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// The length of the recording, zero while it is loading.
+    pub fn duration(&self) -> Duration {
+        match &self.state {
+            State::Ready(playback) => playback.cast.duration(),
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// Returns the error which prevented the recording from loading, if there was one.
+    pub fn error(&self) -> Option<&io::Error> {
+        match &self.state {
+            State::Failed(err) => Some(err),
+            _ => None,
+        }
+    }
+
+    pub fn get_title(&self) -> &str {
+        self.display.get_title()
+    }
+
+    #[must_use]
+    pub fn focus<T>(&self) -> Task<T>
+    where
+        T: Send + 'static,
+    {
+        self.display.focus()
+    }
+
+    /// Replaces the display with an empty terminal of the recorded size.
+    fn reset(&mut self, cast: &Cast) -> Task<Message> {
+        let (display, display_task) = terminal::Terminal::new();
+        self.display = display
+            .fixed_size(true)
+            .style(self.style.clone())
+            .terminal_config(self.terminal_config.clone());
+        self.display.resize(size_of(&cast.size));
+
+        display_task.map(InnerMessage::Terminal).map(Message)
+    }
+
+    /// Feeds all events up to the current position into the display.
+    fn advance(&mut self) {
+        let position = self.position();
+        let State::Ready(playback) = &mut self.state else {
+            return;
+        };
+
+        while let Some(event) = playback.cast.events.get(playback.next_event)
+            && event.time <= position
+        {
+            match &event.kind {
+                CastEventKind::Output(output) => self.display.advance_bytes(output),
+                CastEventKind::Resize(size) => self.display.resize(size_of(size)),
+                CastEventKind::Input(_) | CastEventKind::Marker(_) => {}
+            }
+
+            playback.next_event += 1;
+        }
+    }
+
+    /// Wakes up once the next event is due.
+    fn schedule(&self) -> Task<Message> {
+        let State::Ready(playback) = &self.state else {
+            return Task::none();
+        };
+
+        if playback.resumed.is_none() {
+            return Task::none();
+        }
+
+        let Some(event) = playback.cast.events.get(playback.next_event) else {
+            // report the end of the recording
+            return Task::done(Message(InnerMessage::Tick(self.generation)));
+        };
+
+        let delay = event
+            .time
+            .saturating_sub(self.position())
+            .div_f32(self.speed);
+        let generation = self.generation;
+
+        Task::future(async move {
+            tokio::time::sleep(delay).await;
+            Message(InnerMessage::Tick(generation))
+        })
+    }
+
+    pub fn view<'a>(&'a self) -> Element<'a, Message> {
+        match &self.state {
+            State::Loading => center(text!("loading recording...")).into(),
+            State::Failed(err) => center(text(err.to_string())).height(Length::Fill).into(),
+            State::Ready(_) => {
+                let display = self.display.view().map(InnerMessage::Terminal).map(Message);
+
+                column![display, self.controls()].spacing(5).into()
+            }
+        }
+    }
+
+    fn controls<'a>(&'a self) -> Element<'a, Message> {
+        let position = self.position();
+        let duration = self.duration();
+
+        let speeds = SPEEDS.into_iter().map(|speed| {
+            let style = if speed == self.speed {
+                button::primary
+            } else {
+                button::secondary
+            };
+
+            button(text!("{speed}x"))
+                .style(style)
+                .on_press(Message(InnerMessage::Speed(speed)))
+                .into()
+        });
+
+        row![
+            button(text(if self.is_paused() { "Play" } else { "Pause" }))
+                .on_press(Message(InnerMessage::TogglePause)),
+            slider(
+                0.0..=duration.as_secs_f32(),
+                position.as_secs_f32(),
+                |seconds| { Message(InnerMessage::Seek(seconds)) }
+            )
+            .step(0.1),
+            text!("{} / {}", format_time(position), format_time(duration)),
+        ]
+        .extend(speeds)
+        .spacing(10)
+        .align_y(Vertical::Center)
+        .into()
+    }
+}
+
+fn size_of(size: &async_pty::TerminalSize) -> Size {
+    Size {
+        cols: size.cols as usize,
+        rows: size.rows as usize,
+        pixel_width: size.pixel_width as usize,
+        pixel_height: size.pixel_height as usize,
+    }
+}
+
+fn format_time(time: Duration) -> String {
+    let seconds = time.as_secs();
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
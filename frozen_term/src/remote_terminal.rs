@@ -0,0 +1,494 @@
+use std::{
+    io,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, ready},
+};
+
+pub use crate::stream_terminal::{Action, Message};
+use crate::{
+    Size, Style, TerminalConfig,
+    stream_terminal::{SizeHandle, StreamTerminal, poll_drain},
+};
+use async_pty::remote::Frame;
+pub use async_pty::{
+    ExitStatus, TerminalSize,
+    remote::{Address, Connection},
+};
+use iced::{Element, Task};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// A terminal connected to a shell on a `frostbyte_server`.
+///
+/// Retrying after the connection failed attaches to the same session again,
+/// which only succeeds if the server persists sessions.
+pub struct RemoteTerminal {
+    inner: StreamTerminal,
+    address: Address,
+    handle: RemoteHandle,
+}
+
+impl RemoteTerminal {
+    /// Starts a new session on the server.
+    pub fn connect(
+        address: Address,
+        key_filter: impl 'static + Fn(&iced::keyboard::Key, &iced::keyboard::Modifiers) -> bool,
+    ) -> (Self, Task<Message>) {
+        Self::open(address, RemoteHandle::default(), key_filter)
+    }
+
+    /// Attaches to a running session, see [`async_pty::remote::list_sessions`].
+    ///
+    /// The output the server kept of the session is shown first.
+    pub fn attach(
+        address: Address,
+        session: u32,
+        key_filter: impl 'static + Fn(&iced::keyboard::Key, &iced::keyboard::Modifiers) -> bool,
+    ) -> (Self, Task<Message>) {
+        let handle = RemoteHandle::default();
+        handle.shared.lock().unwrap().session = Some(session);
+
+        Self::open(address, handle, key_filter)
+    }
+
+    fn open(
+        address: Address,
+        // shared by all connections, so a reconnect still reports the size
+        handle: RemoteHandle,
+        key_filter: impl 'static + Fn(&iced::keyboard::Key, &iced::keyboard::Modifiers) -> bool,
+    ) -> (Self, Task<Message>) {
+        let connect_address = address.clone();
+        let connect_handle = handle.clone();
+        let (inner, task) = StreamTerminal::connect(
+            move || {
+                let address = connect_address.clone();
+                let handle = connect_handle.clone();
+                async move {
+                    let connection = Connection::connect(&address).await?;
+                    let session = handle.session();
+                    let stream = RemoteStream::with_handle(connection, handle);
+
+                    Ok(match session {
+                        Some(session) => stream.attach(session),
+                        None => stream,
+                    })
+                }
+            },
+            key_filter,
+        );
+
+        let resize_handle = handle.clone();
+        // the server replays the output of the session when attaching again
+        let inner = inner
+            .on_resize(move |size| resize_handle.resize(size))
+            .reset_on_open();
+
+        (
+            Self {
+                inner,
+                address,
+                handle,
+            },
+            task,
+        )
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.set_style(style);
+        self
+    }
+
+    pub fn set_style(&mut self, style: Style) {
+        self.inner.set_style(style);
+    }
+
+    pub fn terminal_config(mut self, config: TerminalConfig) -> Self {
+        self.set_terminal_config(config);
+        self
+    }
+
+    pub fn set_terminal_config(&mut self, config: TerminalConfig) {
+        self.inner.set_terminal_config(config);
+    }
+
+    pub fn address(&self) -> &Address {
+        &self.address
+    }
+
+    #[must_use]
+    pub fn update(&mut self, message: Message) -> Action {
+        self.inner.update(message)
+    }
+
+    pub fn view<'a>(&'a self) -> Element<'a, Message> {
+        self.inner.view()
+    }
+
+    /// Returns the exit status of the remote process once it has exited.
+    pub fn exit_status(&self) -> Option<ExitStatus> {
+        self.handle.exit_status()
+    }
+
+    /// Returns the id of the session on the server while it is running.
+    pub fn session(&self) -> Option<u32> {
+        self.handle.session()
+    }
+
+    /// Returns the error which stopped the terminal, if there was one.
+    pub fn error(&self) -> Option<&io::Error> {
+        self.inner.error()
+    }
+
+    pub fn get_title(&self) -> &str {
+        self.inner.get_title()
+    }
+
+    #[must_use]
+    pub fn focus<T>(&self) -> Task<T>
+    where
+        T: Send + 'static,
+    {
+        self.inner.focus()
+    }
+}
+
+/// Shares the terminal size, exit status and session id with a [`RemoteStream`].
+#[derive(Debug, Clone, Default)]
+pub struct RemoteHandle {
+    size: SizeHandle,
+    shared: Arc<Mutex<Shared>>,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    exit_status: Option<ExitStatus>,
+    session: Option<u32>,
+}
+
+impl RemoteHandle {
+    pub fn resize(&self, size: Size) {
+        self.size.resize(size);
+    }
+
+    pub fn exit_status(&self) -> Option<ExitStatus> {
+        self.shared.lock().unwrap().exit_status.clone()
+    }
+
+    pub fn session(&self) -> Option<u32> {
+        self.shared.lock().unwrap().session
+    }
+}
+
+/// Input is sent in frames of at most this size, far below what the server accepts.
+const MAX_INPUT_LEN: usize = 64 * 1024;
+
+/// Speaks the remote pty protocol over a byte stream.
+///
+/// Reading returns the output of the remote process and ends once it has exited,
+/// writing sends input to it. Resizes are sent on the next write or flush,
+/// so a writer should keep flushing while it has nothing else to write.
+pub struct RemoteStream<S> {
+    inner: S,
+    // received bytes which don't form a complete frame yet
+    incoming: Vec<u8>,
+    // output which didn't fit into the read buffer
+    output: Vec<u8>,
+    // encoded frames which still have to be written to `inner`
+    outgoing: Vec<u8>,
+    exited: bool,
+    handle: RemoteHandle,
+}
+
+impl<S> RemoteStream<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    pub fn new(inner: S) -> Self {
+        Self::with_handle(inner, RemoteHandle::default())
+    }
+
+    /// Uses an existing handle, e.g. to keep reporting the size after reconnecting.
+    pub fn with_handle(inner: S, handle: RemoteHandle) -> Self {
+        // the new session has to learn the size again
+        handle.size.mark_dirty();
+        handle.shared.lock().unwrap().exit_status = None;
+
+        Self {
+            inner,
+            incoming: Vec::new(),
+            output: Vec::new(),
+            outgoing: Vec::new(),
+            exited: false,
+            handle,
+        }
+    }
+
+    /// Attaches to a running session instead of starting a new one.
+    pub fn attach(mut self, session: u32) -> Self {
+        // has to be the first frame the server receives
+        let mut outgoing = Vec::new();
+        Frame::Attach(session).encode(&mut outgoing);
+        outgoing.append(&mut self.outgoing);
+        self.outgoing = outgoing;
+        self
+    }
+
+    pub fn handle(&self) -> RemoteHandle {
+        self.handle.clone()
+    }
+
+    fn queue_resize(&mut self) {
+        let Some(size) = self.handle.size.take_changed() else {
+            return;
+        };
+
+        Frame::Resize(TerminalSize {
+            cols: size.cols as u16,
+            rows: size.rows as u16,
+            pixel_width: size.pixel_width as u16,
+            pixel_height: size.pixel_height as u16,
+        })
+        .encode(&mut self.outgoing);
+    }
+
+    /// Writes as much of the outgoing buffer as possible, only ready once it is empty.
+    ///
+    /// Only called when writing, reading must not register its waker for the write readiness of `inner`.
+    fn poll_outgoing(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.handle.size.register_writer(cx.waker());
+        self.queue_resize();
+        poll_drain(&mut self.inner, &mut self.outgoing, cx)
+    }
+}
+
+impl<S> AsyncRead for RemoteStream<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        let mut raw = [0u8; 8192];
+        loop {
+            if !this.output.is_empty() {
+                let len = this.output.len().min(buf.remaining());
+                buf.put_slice(&this.output[..len]);
+                this.output.drain(..len);
+                return Poll::Ready(Ok(()));
+            }
+
+            if this.exited {
+                return Poll::Ready(Ok(()));
+            }
+
+            while let Some(frame) = Frame::decode(&mut this.incoming)? {
+                match frame {
+                    Frame::Output(output) => this.output.extend_from_slice(&output),
+                    Frame::Exit(status) => {
+                        let mut shared = this.handle.shared.lock().unwrap();
+                        shared.exit_status = status;
+                        shared.session = None;
+                        this.exited = true;
+                    }
+                    Frame::Attached(session) => {
+                        this.handle.shared.lock().unwrap().session = Some(session);
+                    }
+                    // only sent by clients or in reply to a list request
+                    Frame::Input(_)
+                    | Frame::Resize(_)
+                    | Frame::List
+                    | Frame::Attach(_)
+                    | Frame::Close
+                    | Frame::Kill(_)
+                    | Frame::Sessions(_) => {}
+                }
+            }
+
+            if !this.output.is_empty() || this.exited {
+                continue;
+            }
+
+            let mut raw_buf = ReadBuf::new(&mut raw);
+            ready!(Pin::new(&mut this.inner).poll_read(cx, &mut raw_buf))?;
+            if raw_buf.filled().is_empty() {
+                return Poll::Ready(Ok(()));
+            }
+            this.incoming.extend_from_slice(raw_buf.filled());
+        }
+    }
+}
+
+impl<S> AsyncWrite for RemoteStream<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.poll_outgoing(cx))?;
+
+        // larger writes are split, the server rejects frames above its limit
+        let len = buf.len().min(MAX_INPUT_LEN);
+        Frame::Input(buf[..len].to_vec()).encode(&mut this.outgoing);
+
+        // the rest is written on the next write or flush
+        if let Poll::Ready(Err(err)) = this.poll_outgoing(cx) {
+            return Poll::Ready(Err(err));
+        }
+
+        Poll::Ready(Ok(len))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_outgoing(cx))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_outgoing(cx))?;
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use async_pty::remote::{read_frame, write_frame};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream, duplex};
+
+    use super::*;
+
+    fn connect() -> (RemoteStream<DuplexStream>, DuplexStream) {
+        let (client, server) = duplex(MAX_INPUT_LEN * 4);
+        (RemoteStream::new(client), server)
+    }
+
+    async fn next_frame(server: &mut DuplexStream) -> Frame {
+        tokio::time::timeout(Duration::from_secs(5), read_frame(server))
+            .await
+            .expect("nothing was sent")
+            .unwrap()
+            .expect("the stream ended")
+    }
+
+    #[tokio::test]
+    async fn attach_is_sent_before_the_size() {
+        let (stream, mut server) = connect();
+        let mut stream = stream.attach(7);
+
+        stream.handle().resize(Size {
+            cols: 120,
+            rows: 40,
+            pixel_width: 960,
+            pixel_height: 640,
+        });
+        stream.write_all(b"ls\r").await.unwrap();
+        stream.flush().await.unwrap();
+
+        assert!(matches!(next_frame(&mut server).await, Frame::Attach(7)));
+        let Frame::Resize(size) = next_frame(&mut server).await else {
+            panic!("expected resize");
+        };
+        assert_eq!(
+            (size.cols, size.rows, size.pixel_width, size.pixel_height),
+            (120, 40, 960, 640)
+        );
+        let Frame::Input(input) = next_frame(&mut server).await else {
+            panic!("expected input");
+        };
+        assert_eq!(input, b"ls\r");
+    }
+
+    #[tokio::test]
+    async fn large_input_is_split() {
+        let (mut stream, mut server) = connect();
+
+        let input = vec![b'x'; MAX_INPUT_LEN * 2 + 1];
+        let written = stream.write(&input).await.unwrap();
+        assert_eq!(written, MAX_INPUT_LEN);
+
+        stream.write_all(&input[written..]).await.unwrap();
+        stream.flush().await.unwrap();
+
+        let mut received = Vec::new();
+        while received.len() < input.len() {
+            let Frame::Input(chunk) = next_frame(&mut server).await else {
+                panic!("expected input");
+            };
+            assert!(chunk.len() <= MAX_INPUT_LEN);
+            received.extend(chunk);
+        }
+        assert_eq!(received, input);
+    }
+
+    #[tokio::test]
+    async fn output_until_exit() {
+        let (mut stream, mut server) = connect();
+        let handle = stream.handle();
+
+        write_frame(&mut server, &Frame::Attached(3)).await.unwrap();
+        write_frame(&mut server, &Frame::Output(b"hello ".to_vec()))
+            .await
+            .unwrap();
+        write_frame(&mut server, &Frame::Output(b"world".to_vec()))
+            .await
+            .unwrap();
+        write_frame(
+            &mut server,
+            &Frame::Exit(Some(ExitStatus::with_exit_code(2))),
+        )
+        .await
+        .unwrap();
+
+        let mut output = String::new();
+        stream.read_to_string(&mut output).await.unwrap();
+
+        assert_eq!(output, "hello world");
+        assert_eq!(handle.exit_status().unwrap().exit_code(), 2);
+        // the session is gone once the process exited
+        assert_eq!(handle.session(), None);
+    }
+
+    /// Resizes are sent by the writer, which has to be woken while it is idle.
+    #[tokio::test]
+    async fn idle_writer_sends_resizes() {
+        use std::future::poll_fn;
+
+        let (stream, mut server) = connect();
+        let handle = stream.handle();
+        let (_reader, mut writer) = tokio::io::split(stream);
+
+        // flushes whenever it is woken, like the writer of a `StreamTerminal`
+        let writer = tokio::spawn(async move {
+            poll_fn(|cx| match Pin::new(&mut writer).poll_flush(cx) {
+                Poll::Ready(Err(err)) => Poll::Ready(err),
+                _ => Poll::Pending,
+            })
+            .await
+        });
+        tokio::task::yield_now().await;
+
+        handle.resize(Size {
+            cols: 100,
+            rows: 30,
+            pixel_width: 0,
+            pixel_height: 0,
+        });
+        let Frame::Resize(size) = next_frame(&mut server).await else {
+            panic!("expected resize");
+        };
+        assert_eq!((size.cols, size.rows), (100, 30));
+
+        writer.abort();
+    }
+}
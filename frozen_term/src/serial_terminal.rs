@@ -0,0 +1,193 @@
+use crate::{Style, TerminalConfig, stream_terminal::StreamTerminal};
+pub use crate::stream_terminal::{Action, Message};
+use iced::{Element, Task};
+use tokio_serial::SerialPortBuilderExt;
+pub use tokio_serial::{DataBits, FlowControl, Parity, StopBits};
+
+/// Describes how to open a serial port.
+///
+/// Defaults to 8N1 without flow control.
+#[derive(Debug, Clone)]
+pub struct SerialConfig {
+    path: String,
+    baud_rate: u32,
+    data_bits: DataBits,
+    parity: Parity,
+    stop_bits: StopBits,
+    flow_control: FlowControl,
+}
+
+impl SerialConfig {
+    /// `path` is the device to open, e.g. `/dev/ttyUSB0` or `COM3`.
+    pub fn new(path: impl Into<String>, baud_rate: u32) -> Self {
+        Self {
+            path: path.into(),
+            baud_rate,
+            data_bits: DataBits::Eight,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+            flow_control: FlowControl::None,
+        }
+    }
+
+    pub fn data_bits(mut self, data_bits: DataBits) -> Self {
+        self.data_bits = data_bits;
+        self
+    }
+
+    pub fn parity(mut self, parity: Parity) -> Self {
+        self.parity = parity;
+        self
+    }
+
+    pub fn stop_bits(mut self, stop_bits: StopBits) -> Self {
+        self.stop_bits = stop_bits;
+        self
+    }
+
+    pub fn flow_control(mut self, flow_control: FlowControl) -> Self {
+        self.flow_control = flow_control;
+        self
+    }
+
+    pub fn get_path(&self) -> &str {
+        &self.path
+    }
+
+    fn open(&self) -> std::io::Result<tokio_serial::SerialStream> {
+        let port = tokio_serial::new(&self.path, self.baud_rate)
+            .data_bits(self.data_bits)
+            .parity(self.parity)
+            .stop_bits(self.stop_bits)
+            .flow_control(self.flow_control)
+            .open_native_async()?;
+
+        Ok(port)
+    }
+}
+
+/// A terminal connected to a serial port, e.g. the console of an embedded board.
+///
+/// The port can be reopened with the retry button if the device was unplugged.
+pub struct SerialTerminal {
+    inner: StreamTerminal,
+    config: SerialConfig,
+}
+
+impl SerialTerminal {
+    pub fn open(
+        config: SerialConfig,
+        key_filter: impl 'static + Fn(&iced::keyboard::Key, &iced::keyboard::Modifiers) -> bool,
+    ) -> (Self, Task<Message>) {
+        let open_config = config.clone();
+        let (inner, task) = StreamTerminal::connect(
+            move || {
+                let config = open_config.clone();
+                async move { config.open() }
+            },
+            key_filter,
+        );
+
+        (Self { inner, config }, task)
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.set_style(style);
+        self
+    }
+
+    pub fn set_style(&mut self, style: Style) {
+        self.inner.set_style(style);
+    }
+
+    pub fn terminal_config(mut self, config: TerminalConfig) -> Self {
+        self.set_terminal_config(config);
+        self
+    }
+
+    pub fn set_terminal_config(&mut self, config: TerminalConfig) {
+        self.inner.set_terminal_config(config);
+    }
+
+    pub fn config(&self) -> &SerialConfig {
+        &self.config
+    }
+
+    #[must_use]
+    pub fn update(&mut self, message: Message) -> Action {
+        self.inner.update(message)
+    }
+
+    pub fn view<'a>(&'a self) -> Element<'a, Message> {
+        self.inner.view()
+    }
+
+    /// Returns the error which stopped the terminal, if there was one.
+    pub fn error(&self) -> Option<&std::io::Error> {
+        self.inner.error()
+    }
+
+    pub fn get_title(&self) -> &str {
+        self.inner.get_title()
+    }
+
+    #[must_use]
+    pub fn focus<T>(&self) -> Task<T>
+    where
+        T: Send + 'static,
+    {
+        self.inner.focus()
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_serial::{SerialPort, SerialStream};
+
+    use super::*;
+
+    // a pseudo-terminal stands in for the device, its other end is the board
+    #[tokio::test]
+    async fn pty_as_serial_device() {
+        tokio::time::timeout(Duration::from_secs(10), async {
+            let (mut board, device) = SerialStream::pair().unwrap();
+            let path = device.name().unwrap();
+            drop(device);
+
+            let config = SerialConfig::new(path, 115_200).parity(Parity::None);
+            let mut port = config.open().unwrap();
+            assert_eq!(port.baud_rate().unwrap(), 115_200);
+
+            board.write_all(b"login: ").await.unwrap();
+            let mut buffer = [0; 7];
+            port.read_exact(&mut buffer).await.unwrap();
+            assert_eq!(&buffer, b"login: ");
+
+            // raw mode, so nothing is echoed or translated on the way
+            port.write_all(b"root\r").await.unwrap();
+            let mut buffer = [0; 5];
+            board.read_exact(&mut buffer).await.unwrap();
+            assert_eq!(&buffer, b"root\r");
+
+            // unplugging ends the output instead of blocking forever,
+            // linux reports an error, others the end of the stream
+            drop(board);
+            let mut rest = Vec::new();
+            let _ = port.read_to_end(&mut rest).await;
+            assert!(rest.is_empty());
+        })
+        .await
+        .unwrap();
+    }
+
+    #[test]
+    fn missing_device() {
+        let err = SerialConfig::new("/dev/frozen_term-missing", 9600)
+            .open()
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,507 @@
+use std::{future::poll_fn, io, pin::Pin, sync::Arc, task::Poll};
+
+use crate::{Size, Style, TerminalConfig, terminal};
+use iced::{
+    Element, Length, Task,
+    alignment::Horizontal,
+    task::{Handle, sipper},
+    widget::{button, center, column, text},
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    sync::mpsc,
+};
+
+#[cfg(any(feature = "telnet-terminal", feature = "remote-terminal"))]
+mod size_handle;
+
+#[cfg(any(feature = "telnet-terminal", feature = "remote-terminal"))]
+pub use size_handle::SizeHandle;
+#[cfg(any(feature = "telnet-terminal", feature = "remote-terminal"))]
+pub(crate) use size_handle::poll_drain;
+
+/// A full reset, which also leaves the alternate screen, followed by erasing the scrollback.
+const RESET: &[u8] = b"\x1bc\x1b[3J";
+
+/// Input waiting for the writer, further input is queued until there is space again.
+const INPUT_CAPACITY: usize = 10;
+
+#[derive(Debug, Clone)]
+pub struct Message(InnerMessage);
+
+#[derive(Debug, Clone)]
+enum InnerMessage {
+    Opened(Arc<Connection>),
+    // opening the stream failed
+    Failed(Arc<io::Error>),
+    Retry,
+    Terminal(terminal::Message),
+    // tagged with the generation of the connection, so messages of a replaced one are ignored
+    Output(usize, Vec<u8>),
+    OutputClosed(usize),
+    ConnectionFailed(usize, Arc<io::Error>),
+    InputReady(usize),
+}
+
+pub enum Action {
+    Run(Task<Message>),
+    IdChanged,
+    /// A hyperlink in the output was Ctrl+clicked.
+    OpenUrl(String),
+    /// The stream was closed by the other side.
+    Close,
+    None,
+}
+
+// the halves of a split stream are Sync as long as the stream is Send
+struct Connection {
+    reader: Box<dyn AsyncRead + Send + Sync + Unpin>,
+    writer: Box<dyn AsyncWrite + Send + Sync + Unpin>,
+}
+
+impl std::fmt::Debug for Connection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Connection").finish_non_exhaustive()
+    }
+}
+
+impl Connection {
+    fn new<S>(stream: S) -> Self
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let (reader, writer) = tokio::io::split(stream);
+
+        Self {
+            reader: Box::new(reader),
+            writer: Box::new(writer),
+        }
+    }
+}
+
+enum State {
+    Opening,
+    Active {
+        input: mpsc::Sender<Vec<u8>>,
+        // input which arrived while the writer was busy, it is sent once there is space to keep the order
+        queued: Option<Vec<u8>>,
+        // aborts the io tasks once the connection is no longer used
+        _handle: Handle,
+    },
+    Closed,
+    Failed(Arc<io::Error>),
+}
+
+/// A terminal connected to any byte stream, e.g. a serial port or a network connection.
+///
+/// Output of the stream is shown in the terminal and input of the terminal is written to the stream.
+/// As plain streams have no concept of a terminal size, resizes are reported through [`StreamTerminal::on_resize`].
+pub struct StreamTerminal {
+    state: State,
+    display: terminal::Terminal,
+    // used to retry after a failure, only available if the terminal opened the stream itself
+    reopen: Option<Box<dyn Fn() -> Task<Message>>>,
+    on_resize: Option<Box<dyn Fn(Size)>>,
+    size: Option<Size>,
+    reset_on_open: bool,
+    // increased whenever a stream is attached, e.g. after retrying
+    generation: usize,
+}
+
+impl StreamTerminal {
+    /// Connects the terminal to an already opened stream.
+    pub fn new<S>(
+        stream: S,
+        key_filter: impl 'static + Fn(&iced::keyboard::Key, &iced::keyboard::Modifiers) -> bool,
+    ) -> (Self, Task<Message>)
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let opened = Message(InnerMessage::Opened(Arc::new(Connection::new(stream))));
+
+        Self::with_opener(Task::done(opened), None, key_filter)
+    }
+
+    /// Opens the stream using `connect`, which is called again if the user retries after a failure.
+    pub fn connect<F, S>(
+        connect: impl 'static + Fn() -> F,
+        key_filter: impl 'static + Fn(&iced::keyboard::Key, &iced::keyboard::Modifiers) -> bool,
+    ) -> (Self, Task<Message>)
+    where
+        F: Future<Output = io::Result<S>> + Send + 'static,
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let open = move || {
+            Task::future(connect())
+                .map(|result| match result {
+                    Ok(stream) => InnerMessage::Opened(Arc::new(Connection::new(stream))),
+                    Err(err) => InnerMessage::Failed(Arc::new(err)),
+                })
+                .map(Message)
+        };
+
+        Self::with_opener(open(), Some(Box::new(open)), key_filter)
+    }
+
+    fn with_opener(
+        open_task: Task<Message>,
+        reopen: Option<Box<dyn Fn() -> Task<Message>>>,
+        key_filter: impl 'static + Fn(&iced::keyboard::Key, &iced::keyboard::Modifiers) -> bool,
+    ) -> (Self, Task<Message>) {
+        let (display, display_task) = terminal::Terminal::new();
+        let display = display.key_filter(key_filter);
+
+        (
+            Self {
+                state: State::Opening,
+                display,
+                reopen,
+                on_resize: None,
+                size: None,
+                reset_on_open: false,
+                generation: 0,
+            },
+            Task::batch([
+                display_task.map(InnerMessage::Terminal).map(Message),
+                open_task,
+            ]),
+        )
+    }
+
+    /// Called whenever the terminal is resized and after the stream was (re)opened,
+    /// e.g. to tell the other side about the new size.
+    pub fn on_resize(mut self, on_resize: impl 'static + Fn(Size)) -> Self {
+        self.on_resize = Some(Box::new(on_resize));
+        self
+    }
+
+    /// Clears the display whenever the stream is (re)opened,
+    /// e.g. because the other side sends everything it has shown so far.
+    pub fn reset_on_open(mut self) -> Self {
+        self.reset_on_open = true;
+        self
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.set_style(style);
+        self
+    }
+
+    pub fn set_style(&mut self, style: Style) {
+        self.display.set_style(style);
+    }
+
+    pub fn terminal_config(mut self, config: TerminalConfig) -> Self {
+        self.set_terminal_config(config);
+        self
+    }
+
+    pub fn set_terminal_config(&mut self, config: TerminalConfig) {
+        self.display.set_terminal_config(config);
+    }
+
+    #[must_use]
+    pub fn update(&mut self, message: Message) -> Action {
+        match message.0 {
+            InnerMessage::Opened(arc) => {
+                let connection = Arc::into_inner(arc).unwrap();
+                if self.reset_on_open {
+                    self.display.advance_bytes(RESET);
+                }
+                let task = self.attach(connection);
+
+                if let (Some(on_resize), Some(size)) = (&self.on_resize, self.size) {
+                    on_resize(size);
+                }
+
+                Action::Run(task)
+            }
+            InnerMessage::Failed(err) => {
+                self.state = State::Failed(err);
+                Action::None
+            }
+            InnerMessage::Retry => match &self.reopen {
+                Some(reopen) => {
+                    self.state = State::Opening;
+                    Action::Run(reopen())
+                }
+                None => Action::None,
+            },
+            InnerMessage::Terminal(message) => match self.display.update(message) {
+                terminal::Action::None => Action::None,
+                terminal::Action::Run(task) => {
+                    Action::Run(task.map(InnerMessage::Terminal).map(Message))
+                }
+                terminal::Action::IdChanged => Action::IdChanged,
+                terminal::Action::OpenUrl(url) => Action::OpenUrl(url),
+                terminal::Action::Input(input) => self.write(input),
+                terminal::Action::Resize(size) => {
+                    self.size = Some(size);
+                    if let Some(on_resize) = &self.on_resize {
+                        on_resize(size);
+                    }
+                    Action::None
+                }
+            },
+            InnerMessage::Output(generation, _)
+            | InnerMessage::OutputClosed(generation)
+            | InnerMessage::ConnectionFailed(generation, _)
+            | InnerMessage::InputReady(generation)
+                if generation != self.generation =>
+            {
+                Action::None
+            }
+            InnerMessage::Output(_, output) => {
+                self.display.advance_bytes(output);
+                Action::None
+            }
+            InnerMessage::OutputClosed(_) => {
+                self.state = State::Closed;
+                Action::Close
+            }
+            InnerMessage::ConnectionFailed(_, err) => {
+                self.state = State::Failed(err);
+                Action::None
+            }
+            InnerMessage::InputReady(_) => {
+                let State::Active { queued, .. } = &mut self.state else {
+                    return Action::None;
+                };
+
+                match queued.take() {
+                    Some(queued) => self.write(queued),
+                    None => Action::None,
+                }
+            }
+        }
+    }
+
+    fn attach(&mut self, connection: Connection) -> Task<Message> {
+        let Connection { reader, writer } = connection;
+        self.generation += 1;
+        let generation = self.generation;
+
+        let read = sipper(|mut sender| async move {
+            let mut reader = reader;
+            let mut buffer = vec![0u8; 4096];
+            loop {
+                match reader.read(&mut buffer).await {
+                    Ok(0) => {
+                        sender.send(InnerMessage::OutputClosed(generation)).await;
+                        break;
+                    }
+                    Ok(bytes) => {
+                        sender
+                            .send(InnerMessage::Output(generation, buffer[..bytes].to_vec()))
+                            .await;
+                    }
+                    Err(err) => {
+                        sender
+                            .send(InnerMessage::ConnectionFailed(generation, Arc::new(err)))
+                            .await;
+                        break;
+                    }
+                }
+            }
+        });
+
+        let (input_send, input_recv) = mpsc::channel::<Vec<u8>>(INPUT_CAPACITY);
+        let write = Task::future(async move {
+            let mut writer = writer;
+            let mut input_recv = input_recv;
+            loop {
+                // flushing while idle sends data the stream queued itself, e.g. protocol replies
+                let input = poll_fn(|cx| match input_recv.poll_recv(cx) {
+                    Poll::Ready(input) => Poll::Ready(Ok(input)),
+                    Poll::Pending => match Pin::new(&mut writer).poll_flush(cx) {
+                        Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+                        _ => Poll::Pending,
+                    },
+                })
+                .await;
+
+                let result = match input {
+                    Ok(Some(input)) => write_flushed(&mut writer, &input).await,
+                    Ok(None) => return None,
+                    Err(err) => Err(err),
+                };
+
+                if let Err(err) = result {
+                    return Some(Arc::new(err));
+                }
+            }
+        })
+        .and_then(move |err| Task::done(InnerMessage::ConnectionFailed(generation, err)));
+
+        let (task, handle) = Task::batch([Task::stream(read), write]).abortable();
+
+        self.state = State::Active {
+            input: input_send,
+            queued: None,
+            _handle: handle.abort_on_drop(),
+        };
+
+        task.map(Message)
+    }
+
+    fn write(&mut self, input: Vec<u8>) -> Action {
+        let State::Active {
+            input: input_send,
+            queued,
+            ..
+        } = &mut self.state
+        else {
+            return Action::None;
+        };
+
+        if let Some(queued) = queued {
+            queued.extend(input);
+            return Action::None;
+        }
+
+        match input_send.try_send(input) {
+            Ok(()) => Action::None,
+            // further input is queued until the writer caught up, which keeps its order
+            Err(mpsc::error::TrySendError::Full(input)) => {
+                *queued = Some(input);
+
+                let input_send = input_send.clone();
+                let generation = self.generation;
+                Action::Run(Task::future(async move {
+                    let _ = input_send.reserve().await;
+                    Message(InnerMessage::InputReady(generation))
+                }))
+            }
+            // the writer only stops after reporting an error, which changes the state
+            Err(mpsc::error::TrySendError::Closed(_)) => Action::None,
+        }
+    }
+
+    pub fn view<'a>(&'a self) -> Element<'a, Message> {
+        match &self.state {
+            State::Opening => center(text!("connecting...")).into(),
+            State::Active { .. } => self.display.view().map(InnerMessage::Terminal).map(Message),
+            State::Closed => center(text!("connection closed"))
+                .height(Length::Fill)
+                .into(),
+            State::Failed(err) => {
+                let mut content = column![text(err.to_string())]
+                    .spacing(10)
+                    .align_x(Horizontal::Center);
+
+                if self.reopen.is_some() {
+                    content =
+                        content.push(button(text("Retry")).on_press(Message(InnerMessage::Retry)));
+                }
+
+                center(content).height(Length::Fill).into()
+            }
+        }
+    }
+
+    /// Returns the error which stopped the terminal, if there was one.
+    pub fn error(&self) -> Option<&io::Error> {
+        match &self.state {
+            State::Failed(err) => Some(err),
+            _ => None,
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        matches!(self.state, State::Closed)
+    }
+
+    pub fn get_title(&self) -> &str {
+        self.display.get_title()
+    }
+
+    #[must_use]
+    pub fn focus<T>(&self) -> Task<T>
+    where
+        T: Send + 'static,
+    {
+        self.display.focus()
+    }
+}
+
+async fn write_flushed(writer: &mut (impl AsyncWrite + Unpin), input: &[u8]) -> io::Result<()> {
+    writer.write_all(input).await?;
+    writer.flush().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the io tasks are not running, so nothing is read or written
+    fn open(terminal: &mut StreamTerminal) -> tokio::io::DuplexStream {
+        let (local, remote) = tokio::io::duplex(64);
+        let opened = InnerMessage::Opened(Arc::new(Connection::new(local)));
+        let Action::Run(_) = terminal.update(Message(opened)) else {
+            panic!("expected the io tasks");
+        };
+
+        remote
+    }
+
+    fn new_terminal() -> StreamTerminal {
+        StreamTerminal::with_opener(Task::none(), None, |_, _| false).0
+    }
+
+    #[test]
+    fn messages_of_a_replaced_stream_are_ignored() {
+        let mut terminal = new_terminal();
+        let _first = open(&mut terminal);
+        let _second = open(&mut terminal);
+        let (old, current) = (terminal.generation - 1, terminal.generation);
+        let title = terminal.get_title().to_string();
+
+        let error = || Arc::new(io::Error::from(io::ErrorKind::ConnectionReset));
+        for message in [
+            InnerMessage::Output(old, b"\x1b]0;old\x07".to_vec()),
+            InnerMessage::OutputClosed(old),
+            InnerMessage::ConnectionFailed(old, error()),
+        ] {
+            assert!(matches!(terminal.update(Message(message)), Action::None));
+        }
+        assert_eq!(terminal.get_title(), title);
+        assert!(!terminal.is_closed());
+        assert!(terminal.error().is_none());
+
+        let output = InnerMessage::Output(current, b"\x1b]0;current\x07".to_vec());
+        let _ = terminal.update(Message(output));
+        assert_eq!(terminal.get_title(), "current");
+
+        let _ = terminal.update(Message(InnerMessage::ConnectionFailed(current, error())));
+        assert!(terminal.error().is_some());
+    }
+
+    #[test]
+    fn input_is_queued_while_the_writer_is_busy() {
+        let mut terminal = new_terminal();
+        let _remote = open(&mut terminal);
+        let generation = terminal.generation;
+
+        let mut type_input = |input: &[u8]| {
+            let message = InnerMessage::Terminal(terminal::Message::input(input));
+            terminal.update(Message(message))
+        };
+
+        for _ in 0..INPUT_CAPACITY {
+            assert!(matches!(type_input(b"x"), Action::None));
+        }
+        let Action::Run(_) = type_input(b"a") else {
+            panic!("expected to wait for the writer");
+        };
+        assert!(matches!(type_input(b"b"), Action::None));
+
+        // still no space, so the input goes back to the queue
+        let Action::Run(_) = terminal.update(Message(InnerMessage::InputReady(generation))) else {
+            panic!("expected to wait for the writer again");
+        };
+        let State::Active { queued, .. } = &terminal.state else {
+            panic!("expected the stream to be active");
+        };
+        assert_eq!(queued.as_deref(), Some(&b"ab"[..]));
+    }
+}
@@ -0,0 +1,88 @@
+use std::{
+    io,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker, ready},
+};
+
+use tokio::io::AsyncWrite;
+
+use crate::Size;
+
+/// Reports the terminal size to a stream which tells the other side about it,
+/// e.g. a [`TelnetStream`](crate::telnet_terminal::TelnetStream).
+///
+/// The size is sent by the writing half of the stream, which is woken whenever it changes.
+#[derive(Debug, Clone, Default)]
+pub struct SizeHandle {
+    shared: Arc<Mutex<Shared>>,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    size: Option<Size>,
+    // the size changed since it was last sent
+    dirty: bool,
+    // wakes the writer, which sends the size and other pending protocol data
+    writer: Option<Waker>,
+}
+
+impl SizeHandle {
+    pub fn resize(&self, size: Size) {
+        let mut shared = self.shared.lock().unwrap();
+        shared.size = Some(size);
+        shared.dirty = true;
+        if let Some(waker) = shared.writer.take() {
+            waker.wake();
+        }
+    }
+
+    /// Sends the size again, e.g. because the other side was not able to receive it until now.
+    pub(crate) fn mark_dirty(&self) {
+        self.shared.lock().unwrap().dirty = true;
+        self.wake_writer();
+    }
+
+    /// Returns the size if it changed since it was last taken.
+    pub(crate) fn take_changed(&self) -> Option<Size> {
+        let mut shared = self.shared.lock().unwrap();
+        let size = shared.size.filter(|_| shared.dirty)?;
+        shared.dirty = false;
+        Some(size)
+    }
+
+    pub(crate) fn register_writer(&self, waker: &Waker) {
+        let mut shared = self.shared.lock().unwrap();
+        match &mut shared.writer {
+            Some(writer) => writer.clone_from(waker),
+            None => shared.writer = Some(waker.clone()),
+        }
+    }
+
+    /// Wakes the writer to send data queued while reading, e.g. replies to the negotiation.
+    pub(crate) fn wake_writer(&self) {
+        if let Some(waker) = self.shared.lock().unwrap().writer.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Writes as much of `outgoing` as possible, only ready once it is empty.
+pub(crate) fn poll_drain<S>(
+    inner: &mut S,
+    outgoing: &mut Vec<u8>,
+    cx: &mut Context<'_>,
+) -> Poll<io::Result<()>>
+where
+    S: AsyncWrite + Unpin,
+{
+    while !outgoing.is_empty() {
+        let written = ready!(Pin::new(&mut *inner).poll_write(cx, outgoing))?;
+        if written == 0 {
+            return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+        }
+        outgoing.drain(..written);
+    }
+
+    Poll::Ready(Ok(()))
+}
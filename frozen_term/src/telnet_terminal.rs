@@ -0,0 +1,757 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll, Waker, ready},
+};
+
+pub use crate::stream_terminal::{Action, Message, SizeHandle};
+use crate::{
+    Style, TerminalConfig,
+    stream_terminal::{StreamTerminal, poll_drain},
+};
+use iced::{Element, Task};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::TcpStream,
+};
+
+const SE: u8 = 240;
+const SB: u8 = 250;
+const WILL: u8 = 251;
+const WONT: u8 = 252;
+const DO: u8 = 253;
+const DONT: u8 = 254;
+const IAC: u8 = 255;
+
+const OPTION_ECHO: u8 = 1;
+const OPTION_SGA: u8 = 3;
+const OPTION_TTYPE: u8 = 24;
+const OPTION_NAWS: u8 = 31;
+
+const TTYPE_IS: u8 = 0;
+const TTYPE_SEND: u8 = 1;
+
+// subnegotiations we support are short, anything longer is cut off
+const MAX_SUBNEGOTIATION: usize = 256;
+
+/// A terminal connected to a telnet server, e.g. a console server or lab equipment.
+pub struct TelnetTerminal {
+    inner: StreamTerminal,
+    address: String,
+}
+
+impl TelnetTerminal {
+    /// Connects to `address`, e.g. `192.168.0.10:23`.
+    pub fn connect(
+        address: impl Into<String>,
+        key_filter: impl 'static + Fn(&iced::keyboard::Key, &iced::keyboard::Modifiers) -> bool,
+    ) -> (Self, Task<Message>) {
+        let address = address.into();
+        // shared by all connections, so a reconnect still reports the size
+        let size = SizeHandle::default();
+
+        let connect_address = address.clone();
+        let connect_size = size.clone();
+        let (inner, task) = StreamTerminal::connect(
+            move || {
+                let address = connect_address.clone();
+                let size = connect_size.clone();
+                async move {
+                    let stream = TcpStream::connect(address).await?;
+                    stream.set_nodelay(true)?;
+                    Ok(TelnetStream::with_size_handle(stream, size))
+                }
+            },
+            key_filter,
+        );
+
+        let inner = inner.on_resize(move |new_size| size.resize(new_size));
+
+        (Self { inner, address }, task)
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.set_style(style);
+        self
+    }
+
+    pub fn set_style(&mut self, style: Style) {
+        self.inner.set_style(style);
+    }
+
+    pub fn terminal_config(mut self, config: TerminalConfig) -> Self {
+        self.set_terminal_config(config);
+        self
+    }
+
+    pub fn set_terminal_config(&mut self, config: TerminalConfig) {
+        self.inner.set_terminal_config(config);
+    }
+
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    #[must_use]
+    pub fn update(&mut self, message: Message) -> Action {
+        self.inner.update(message)
+    }
+
+    pub fn view<'a>(&'a self) -> Element<'a, Message> {
+        self.inner.view()
+    }
+
+    /// Returns the error which stopped the terminal, if there was one.
+    pub fn error(&self) -> Option<&io::Error> {
+        self.inner.error()
+    }
+
+    pub fn get_title(&self) -> &str {
+        self.inner.get_title()
+    }
+
+    #[must_use]
+    pub fn focus<T>(&self) -> Task<T>
+    where
+        T: Send + 'static,
+    {
+        self.inner.focus()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OptionState {
+    Off,
+    // we asked for the option and wait for the answer
+    Requested,
+    On,
+}
+
+#[derive(Debug)]
+enum ParseState {
+    Data,
+    Iac,
+    Negotiation(u8),
+    Subnegotiation,
+    SubnegotiationData,
+    SubnegotiationIac,
+}
+
+/// Speaks the telnet protocol over a byte stream.
+///
+/// Reading returns the data sent by the server with all protocol bytes removed and queues the answers to the option negotiation.
+/// Writing escapes the data for telnet. Queued answers and the window size are sent on the next write or flush,
+/// so a writer should keep flushing while it has nothing else to write.
+///
+/// If the server refuses to echo, written data is echoed locally and returned by the next read.
+pub struct TelnetStream<S> {
+    inner: S,
+    parse: ParseState,
+    after_cr: bool,
+    // the last written byte was a CR, which is followed by NUL unless the next byte is LF
+    pending_cr: bool,
+    // written data waiting to be read, as the server doesn't echo it
+    echo: Vec<u8>,
+    // woken when there is data to echo
+    reader: Option<Waker>,
+    subnegotiation: Vec<u8>,
+    // options we perform, e.g. NAWS
+    local: [OptionState; 256],
+    // options the server performs, e.g. ECHO
+    remote: [OptionState; 256],
+    // encoded bytes which still have to be written to `inner`
+    outgoing: Vec<u8>,
+    terminal_type: String,
+    // the window size is reported once the server agreed to NAWS
+    size: SizeHandle,
+}
+
+impl<S> TelnetStream<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    pub fn new(inner: S) -> Self {
+        Self::with_size_handle(inner, SizeHandle::default())
+    }
+
+    /// Uses an existing handle, e.g. to keep reporting the size after reconnecting.
+    pub fn with_size_handle(inner: S, size: SizeHandle) -> Self {
+        let mut stream = Self {
+            inner,
+            parse: ParseState::Data,
+            after_cr: false,
+            pending_cr: false,
+            echo: Vec::new(),
+            reader: None,
+            subnegotiation: Vec::new(),
+            local: [OptionState::Off; 256],
+            remote: [OptionState::Off; 256],
+            outgoing: Vec::new(),
+            terminal_type: "XTERM-256COLOR".to_string(),
+            size,
+        };
+
+        // most servers only ask for the window size if it is offered
+        stream.request_local(OPTION_NAWS);
+        stream.request_local(OPTION_TTYPE);
+        stream.request_remote(OPTION_SGA);
+        // otherwise typed input is only visible if the server echoes it without being asked
+        stream.request_remote(OPTION_ECHO);
+
+        stream
+    }
+
+    /// The terminal type reported to the server, defaults to `XTERM-256COLOR`.
+    pub fn terminal_type(mut self, terminal_type: impl Into<String>) -> Self {
+        self.terminal_type = terminal_type.into();
+        self
+    }
+
+    pub fn size_handle(&self) -> SizeHandle {
+        self.size.clone()
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    fn request_local(&mut self, option: u8) {
+        self.local[option as usize] = OptionState::Requested;
+        self.outgoing.extend_from_slice(&[IAC, WILL, option]);
+    }
+
+    fn request_remote(&mut self, option: u8) {
+        self.remote[option as usize] = OptionState::Requested;
+        self.outgoing.extend_from_slice(&[IAC, DO, option]);
+    }
+
+    /// Removes the protocol bytes from `input`, writing the data to `output`.
+    ///
+    /// `output` has to be able to hold at least as many bytes as `input`.
+    fn decode(&mut self, input: &[u8], output: &mut ReadBuf<'_>) {
+        for &byte in input {
+            match self.parse {
+                ParseState::Data => match byte {
+                    IAC => self.parse = ParseState::Iac,
+                    // CR NUL is a bare carriage return
+                    0 if self.after_cr => self.after_cr = false,
+                    _ => {
+                        self.after_cr = byte == b'\r';
+                        output.put_slice(&[byte]);
+                    }
+                },
+                ParseState::Iac => {
+                    self.parse = match byte {
+                        IAC => {
+                            self.after_cr = false;
+                            output.put_slice(&[IAC]);
+                            ParseState::Data
+                        }
+                        WILL | WONT | DO | DONT => ParseState::Negotiation(byte),
+                        SB => ParseState::Subnegotiation,
+                        // commands like NOP or GA carry no meaning for us
+                        _ => ParseState::Data,
+                    }
+                }
+                ParseState::Negotiation(command) => {
+                    self.negotiate(command, byte);
+                    self.parse = ParseState::Data;
+                }
+                ParseState::Subnegotiation => {
+                    self.subnegotiation.clear();
+                    self.subnegotiation.push(byte);
+                    self.parse = ParseState::SubnegotiationData;
+                }
+                ParseState::SubnegotiationData => match byte {
+                    IAC => self.parse = ParseState::SubnegotiationIac,
+                    _ => {
+                        if self.subnegotiation.len() < MAX_SUBNEGOTIATION {
+                            self.subnegotiation.push(byte);
+                        }
+                    }
+                },
+                ParseState::SubnegotiationIac => match byte {
+                    SE => {
+                        self.subnegotiate();
+                        self.parse = ParseState::Data;
+                    }
+                    IAC => {
+                        if self.subnegotiation.len() < MAX_SUBNEGOTIATION {
+                            self.subnegotiation.push(IAC);
+                        }
+                        self.parse = ParseState::SubnegotiationData;
+                    }
+                    // malformed, treat as the end of the subnegotiation
+                    _ => self.parse = ParseState::Data,
+                },
+            }
+        }
+    }
+
+    /// Answers a request, only replying if the state changes to avoid negotiation loops.
+    fn negotiate(&mut self, command: u8, option: u8) {
+        let index = option as usize;
+
+        match command {
+            WILL => match self.remote[index] {
+                OptionState::On => {}
+                OptionState::Requested => self.remote[index] = OptionState::On,
+                OptionState::Off => {
+                    if matches!(option, OPTION_ECHO | OPTION_SGA) {
+                        self.remote[index] = OptionState::On;
+                        self.outgoing.extend_from_slice(&[IAC, DO, option]);
+                    } else {
+                        self.outgoing.extend_from_slice(&[IAC, DONT, option]);
+                    }
+                }
+            },
+            WONT => {
+                if self.remote[index] == OptionState::On {
+                    self.outgoing.extend_from_slice(&[IAC, DONT, option]);
+                }
+                self.remote[index] = OptionState::Off;
+            }
+            DO => {
+                match self.local[index] {
+                    OptionState::On => {}
+                    OptionState::Requested => self.local[index] = OptionState::On,
+                    OptionState::Off => {
+                        if matches!(option, OPTION_NAWS | OPTION_TTYPE | OPTION_SGA) {
+                            self.local[index] = OptionState::On;
+                            self.outgoing.extend_from_slice(&[IAC, WILL, option]);
+                        } else {
+                            self.outgoing.extend_from_slice(&[IAC, WONT, option]);
+                        }
+                    }
+                }
+
+                // the server may have missed earlier resizes
+                if option == OPTION_NAWS && self.local[index] == OptionState::On {
+                    self.size.mark_dirty();
+                }
+            }
+            DONT => {
+                if self.local[index] == OptionState::On {
+                    self.outgoing.extend_from_slice(&[IAC, WONT, option]);
+                }
+                self.local[index] = OptionState::Off;
+            }
+            _ => {}
+        }
+    }
+
+    fn subnegotiate(&mut self) {
+        if self.subnegotiation.as_slice() == [OPTION_TTYPE, TTYPE_SEND]
+            && self.local[OPTION_TTYPE as usize] == OptionState::On
+        {
+            self.outgoing
+                .extend_from_slice(&[IAC, SB, OPTION_TTYPE, TTYPE_IS]);
+            escape_into(self.terminal_type.as_bytes(), &mut self.outgoing);
+            self.outgoing.extend_from_slice(&[IAC, SE]);
+        }
+    }
+
+    fn queue_window_size(&mut self) {
+        if self.local[OPTION_NAWS as usize] != OptionState::On {
+            return;
+        }
+
+        let Some(size) = self.size.take_changed() else {
+            return;
+        };
+
+        let [cols_high, cols_low] = (size.cols as u16).to_be_bytes();
+        let [rows_high, rows_low] = (size.rows as u16).to_be_bytes();
+
+        self.outgoing.extend_from_slice(&[IAC, SB, OPTION_NAWS]);
+        escape_into(
+            &[cols_high, cols_low, rows_high, rows_low],
+            &mut self.outgoing,
+        );
+        self.outgoing.extend_from_slice(&[IAC, SE]);
+    }
+
+    /// The server refused to echo, so we have to, as described in RFC 857.
+    fn echoes_locally(&self) -> bool {
+        self.remote[OPTION_ECHO as usize] == OptionState::Off
+    }
+
+    /// Writes as much of the outgoing buffer as possible, only ready once it is empty.
+    ///
+    /// Only called when writing, reading must not register its waker for the write readiness of `inner`.
+    fn poll_outgoing(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.size.register_writer(cx.waker());
+        self.queue_window_size();
+        poll_drain(&mut self.inner, &mut self.outgoing, cx)
+    }
+}
+
+impl<S> AsyncRead for TelnetStream<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        if !this.echo.is_empty() {
+            let len = this.echo.len().min(buf.remaining());
+            buf.put_slice(&this.echo[..len]);
+            this.echo.drain(..len);
+            return Poll::Ready(Ok(()));
+        }
+
+        match &mut this.reader {
+            Some(reader) => reader.clone_from(cx.waker()),
+            None => this.reader = Some(cx.waker().clone()),
+        }
+
+        let mut raw = [0u8; 4096];
+        loop {
+            // decoding never produces more bytes than it consumes
+            let limit = buf.remaining().min(raw.len());
+            let mut raw_buf = ReadBuf::new(&mut raw[..limit]);
+            ready!(Pin::new(&mut this.inner).poll_read(cx, &mut raw_buf))?;
+
+            let input = raw_buf.filled();
+            if input.is_empty() {
+                return Poll::Ready(Ok(()));
+            }
+
+            let before = buf.filled().len();
+            let queued = this.outgoing.len();
+            this.decode(input, buf);
+
+            // the server may wait for the answers before sending more data
+            if this.outgoing.len() > queued {
+                this.size.wake_writer();
+            }
+
+            // only protocol bytes were read, which may have to be answered before more data arrives
+            if buf.filled().len() > before {
+                return Poll::Ready(Ok(()));
+            }
+        }
+    }
+}
+
+impl<S> AsyncWrite for TelnetStream<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.poll_outgoing(cx))?;
+
+        for &byte in buf {
+            // a carriage return has to be followed by LF or NUL, even if the LF comes with the next write
+            if std::mem::take(&mut this.pending_cr) && byte != b'\n' {
+                this.outgoing.push(0);
+            }
+
+            match byte {
+                IAC => this.outgoing.extend_from_slice(&[IAC, IAC]),
+                b'\r' => {
+                    this.outgoing.push(b'\r');
+                    this.pending_cr = true;
+                }
+                _ => this.outgoing.push(byte),
+            }
+        }
+
+        if this.echoes_locally() {
+            for &byte in buf {
+                match byte {
+                    b'\r' => this.echo.extend_from_slice(b"\r\n"),
+                    _ => this.echo.push(byte),
+                }
+            }
+
+            if let Some(reader) = this.reader.take() {
+                reader.wake();
+            }
+        }
+
+        // the rest is written on the next write or flush
+        if let Poll::Ready(Err(err)) = this.poll_outgoing(cx) {
+            return Poll::Ready(Err(err));
+        }
+
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_outgoing(cx))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if std::mem::take(&mut this.pending_cr) {
+            this.outgoing.push(0);
+        }
+        ready!(this.poll_outgoing(cx))?;
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
+
+fn escape_into(data: &[u8], output: &mut Vec<u8>) {
+    for &byte in data {
+        if byte == IAC {
+            output.push(IAC);
+        }
+        output.push(byte);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{future::poll_fn, time::Duration};
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream, duplex};
+
+    use super::*;
+    use crate::Size;
+
+    // sent by the client before anything else
+    const OFFER: [u8; 12] = [
+        IAC,
+        WILL,
+        OPTION_NAWS,
+        IAC,
+        WILL,
+        OPTION_TTYPE,
+        IAC,
+        DO,
+        OPTION_SGA,
+        IAC,
+        DO,
+        OPTION_ECHO,
+    ];
+
+    fn connect() -> (TelnetStream<DuplexStream>, DuplexStream) {
+        let (client, server) = duplex(1024);
+        (TelnetStream::new(client), server)
+    }
+
+    async fn expect(server: &mut DuplexStream, expected: &[u8]) {
+        let mut received = vec![0; expected.len()];
+        tokio::time::timeout(Duration::from_secs(5), server.read_exact(&mut received))
+            .await
+            .expect("nothing was sent")
+            .unwrap();
+        assert_eq!(received, expected);
+    }
+
+    async fn read(stream: &mut TelnetStream<DuplexStream>) -> Vec<u8> {
+        let mut buf = [0; 64];
+        let len = stream.read(&mut buf).await.unwrap();
+        buf[..len].to_vec()
+    }
+
+    #[tokio::test]
+    async fn offers_options_on_first_flush() {
+        let (mut stream, mut server) = connect();
+
+        stream.flush().await.unwrap();
+        expect(&mut server, &OFFER).await;
+    }
+
+    #[tokio::test]
+    async fn escapes_input() {
+        let (mut stream, mut server) = connect();
+
+        stream
+            .write_all(&[b'a', IAC, b'\r', b'b', b'\r', b'\n'])
+            .await
+            .unwrap();
+        stream.flush().await.unwrap();
+
+        expect(&mut server, &OFFER).await;
+        expect(&mut server, &[b'a', IAC, IAC, b'\r', 0, b'b', b'\r', b'\n']).await;
+    }
+
+    #[tokio::test]
+    async fn carriage_return_split_across_writes() {
+        let (mut stream, mut server) = connect();
+
+        stream.write_all(b"a\r").await.unwrap();
+        stream.flush().await.unwrap();
+        stream.write_all(b"\nb\r").await.unwrap();
+        stream.flush().await.unwrap();
+        stream.write_all(b"c\r").await.unwrap();
+        stream.shutdown().await.unwrap();
+
+        expect(&mut server, &OFFER).await;
+        expect(&mut server, b"a\r\nb\r\0c\r\0").await;
+    }
+
+    #[tokio::test]
+    async fn echoes_locally_if_the_server_refuses() {
+        let (mut stream, mut server) = connect();
+
+        // echoed by the server
+        server
+            .write_all(&[IAC, WILL, OPTION_ECHO, b'$'])
+            .await
+            .unwrap();
+        assert_eq!(read(&mut stream).await, b"$");
+        stream.write_all(b"ls\r").await.unwrap();
+        stream.flush().await.unwrap();
+        expect(&mut server, &OFFER).await;
+        expect(&mut server, b"ls\r").await;
+
+        server
+            .write_all(&[IAC, WONT, OPTION_ECHO, b'$'])
+            .await
+            .unwrap();
+        assert_eq!(read(&mut stream).await, b"$");
+        stream.write_all(b"pwd\r").await.unwrap();
+        assert_eq!(read(&mut stream).await, b"pwd\r\n");
+    }
+
+    #[tokio::test]
+    async fn removes_protocol_bytes() {
+        let (mut stream, mut server) = connect();
+
+        server
+            .write_all(&[b'a', IAC, IAC, b'b', IAC, 241, b'\r', 0, b'c'])
+            .await
+            .unwrap();
+
+        let mut output = Vec::new();
+        while output.len() < 5 {
+            output.extend(read(&mut stream).await);
+        }
+        assert_eq!(output, [b'a', IAC, b'b', b'\r', b'c']);
+    }
+
+    #[tokio::test]
+    async fn commands_split_across_reads() {
+        let (mut stream, mut server) = connect();
+
+        server.write_all(&[b'a', IAC]).await.unwrap();
+        assert_eq!(read(&mut stream).await, b"a");
+
+        server.write_all(&[IAC, b'b', IAC]).await.unwrap();
+        assert_eq!(read(&mut stream).await, [IAC, b'b']);
+
+        server.write_all(&[WILL]).await.unwrap();
+        server.write_all(&[42, b'c']).await.unwrap();
+        assert_eq!(read(&mut stream).await, b"c");
+
+        stream.flush().await.unwrap();
+        expect(&mut server, &OFFER).await;
+        expect(&mut server, &[IAC, DONT, 42]).await;
+    }
+
+    #[tokio::test]
+    async fn answers_negotiation() {
+        let (mut stream, mut server) = connect();
+
+        let negotiation = [
+            // requested by us, so there is no answer
+            [IAC, DO, OPTION_TTYPE],
+            [IAC, WILL, OPTION_SGA],
+            [IAC, WILL, OPTION_ECHO],
+            // refused
+            [IAC, WILL, 42],
+            [IAC, DO, 42],
+        ];
+        server.write_all(&negotiation.concat()).await.unwrap();
+        server
+            .write_all(&[IAC, SB, OPTION_TTYPE, TTYPE_SEND, IAC, SE, b'x'])
+            .await
+            .unwrap();
+        assert_eq!(read(&mut stream).await, b"x");
+
+        stream.flush().await.unwrap();
+        expect(&mut server, &OFFER).await;
+        expect(&mut server, &[IAC, DONT, 42, IAC, WONT, 42]).await;
+        expect(&mut server, &[IAC, SB, OPTION_TTYPE, TTYPE_IS]).await;
+        expect(&mut server, b"XTERM-256COLOR").await;
+        expect(&mut server, &[IAC, SE]).await;
+    }
+
+    #[tokio::test]
+    async fn reports_window_size() {
+        let (mut stream, mut server) = connect();
+        let size = stream.size_handle();
+
+        size.resize(Size {
+            cols: 255,
+            rows: 50,
+            pixel_width: 0,
+            pixel_height: 0,
+        });
+        // nothing is sent before the server agreed to NAWS
+        stream.flush().await.unwrap();
+        expect(&mut server, &OFFER).await;
+
+        server
+            .write_all(&[IAC, DO, OPTION_NAWS, b'x'])
+            .await
+            .unwrap();
+        assert_eq!(read(&mut stream).await, b"x");
+        stream.flush().await.unwrap();
+        expect(
+            &mut server,
+            &[IAC, SB, OPTION_NAWS, 0, IAC, IAC, 0, 50, IAC, SE],
+        )
+        .await;
+
+        size.resize(Size {
+            cols: 80,
+            rows: 24,
+            pixel_width: 0,
+            pixel_height: 0,
+        });
+        stream.flush().await.unwrap();
+        expect(&mut server, &[IAC, SB, OPTION_NAWS, 0, 80, 0, 24, IAC, SE]).await;
+    }
+
+    /// The reader only queues answers, an idle writer has to be woken to send them.
+    #[tokio::test]
+    async fn idle_writer_sends_answers() {
+        let (stream, mut server) = connect();
+        let size = stream.size_handle();
+        let (mut reader, mut writer) = tokio::io::split(stream);
+
+        // flushes whenever it is woken, like the writer of a `StreamTerminal`
+        let writer = tokio::spawn(async move {
+            poll_fn(|cx| match Pin::new(&mut writer).poll_flush(cx) {
+                Poll::Ready(Err(err)) => Poll::Ready(err),
+                _ => Poll::Pending,
+            })
+            .await
+        });
+
+        expect(&mut server, &OFFER).await;
+
+        server
+            .write_all(&[IAC, DO, OPTION_NAWS, IAC, WILL, 42, b'x'])
+            .await
+            .unwrap();
+        let mut buf = [0; 8];
+        assert_eq!(reader.read(&mut buf).await.unwrap(), 1);
+        expect(&mut server, &[IAC, DONT, 42]).await;
+
+        size.resize(Size {
+            cols: 100,
+            rows: 30,
+            pixel_width: 0,
+            pixel_height: 0,
+        });
+        expect(&mut server, &[IAC, SB, OPTION_NAWS, 0, 100, 0, 30, IAC, SE]).await;
+
+        writer.abort();
+    }
+}
@@ -8,12 +8,15 @@ use iced::{
 };
 
 use crate::{
-    Style,
+    Style, TerminalConfig,
     scrollbar::Scrollbar,
-    terminal_grid::{PreRenderer, TerminalGrid, VisiblePosition},
+    terminal_grid::{
+        Decoration, PreRenderer, RenderedRow, TerminalGrid, UnderlineStyle, VisiblePosition,
+    },
     wezterm::{WeztermGrid, prerenderer::WeztermPreRenderer},
 };
 
+pub mod config;
 pub mod style;
 use style::CursorShape;
 
@@ -35,6 +38,8 @@ enum InnerMessage {
     StartSelection(VisiblePosition),
     MoveSelection(VisiblePosition),
     EndSelection,
+    Hover(Option<VisiblePosition>),
+    OpenUrl(String),
     ShowContextMenu(iced::Point),
     HideContextMenu,
     ContextMenuCopy,
@@ -42,12 +47,28 @@ enum InnerMessage {
     IdChanged,
 }
 
+// lets the components using the terminal be tested without a window
+#[cfg(test)]
+impl Message {
+    /// Input as the display reports it after a key press.
+    pub(crate) fn input(input: impl Into<Vec<u8>>) -> Self {
+        Self(InnerMessage::Input(input.into()))
+    }
+
+    /// A new size as the widget reports it after layout.
+    pub(crate) fn resize(size: crate::terminal_grid::Size) -> Self {
+        Self(InnerMessage::Resize(size))
+    }
+}
+
 pub enum Action {
     None,
     Run(iced::Task<Message>),
     Resize(crate::terminal_grid::Size),
     Input(Vec<u8>),
     IdChanged,
+    /// A hyperlink was Ctrl+clicked (Cmd+click on macOS), it is up to the application to open it.
+    OpenUrl(String),
 }
 
 pub struct Terminal {
@@ -56,7 +77,13 @@ pub struct Terminal {
     key_filter: Option<Box<dyn Fn(&iced::keyboard::Key, &iced::keyboard::Modifiers) -> bool>>,
     // here to abort the task on drop
     context_menu_position: Option<iced::Point>,
+    // the style as set, `style` also contains the colors changed by applications
+    base_style: Style,
     style: Style,
+    // changes whenever `style` does, so the prerenderer knows to pick it up
+    style_generation: usize,
+    palette_changes: usize,
+    fixed_size: bool,
     _handle: iced::task::Handle,
 }
 
@@ -75,7 +102,11 @@ impl Terminal {
                 id: Id(iced::advanced::widget::Id::unique()),
                 key_filter: None,
                 context_menu_position: None,
+                base_style: Style::default(),
                 style: Style::default(),
+                style_generation: 0,
+                palette_changes: 0,
+                fixed_size: false,
                 _handle: handle,
             },
             task,
@@ -93,7 +124,42 @@ impl Terminal {
     }
 
     pub fn set_style(&mut self, style: Style) {
-        self.style = style;
+        self.grid.set_palette(style.to_wezterm_palette());
+        self.base_style = style;
+        self.update_style();
+    }
+
+    fn update_style(&mut self) {
+        self.style = self
+            .base_style
+            .clone()
+            .with_wezterm_palette(&self.grid.palette());
+        self.style_generation += 1;
+    }
+
+    pub fn terminal_config(mut self, config: TerminalConfig) -> Self {
+        self.set_terminal_config(config);
+        self
+    }
+
+    /// Changes the emulation at runtime, e.g. the scrollback length.
+    pub fn set_terminal_config(&mut self, config: TerminalConfig) {
+        self.grid.set_config(config);
+    }
+
+    /// Stops the terminal from adapting its size to the widget, e.g. to show a recording at its original size.
+    /// The size can still be changed with [`Terminal::resize`].
+    pub fn fixed_size(mut self, fixed_size: bool) -> Self {
+        self.set_fixed_size(fixed_size);
+        self
+    }
+
+    pub fn set_fixed_size(&mut self, fixed_size: bool) {
+        self.fixed_size = fixed_size;
+    }
+
+    pub fn resize(&mut self, size: crate::terminal_grid::Size) {
+        self.grid.resize(size);
     }
 
     /// Allows you to add a filter to stop the terminal from capturing keypresses you want to use for your application.
@@ -138,6 +204,12 @@ impl Terminal {
         B: AsRef<[u8]>,
     {
         self.grid.advance_bytes(bytes.as_ref());
+
+        let palette_changes = self.grid.palette_changes();
+        if palette_changes != self.palette_changes {
+            self.palette_changes = palette_changes;
+            self.update_style();
+        }
     }
 
     #[must_use]
@@ -209,6 +281,11 @@ impl Terminal {
                 self.grid.end_selection();
                 Action::None
             }
+            InnerMessage::Hover(position) => {
+                self.grid.hover(position);
+                Action::None
+            }
+            InnerMessage::OpenUrl(url) => Action::OpenUrl(url),
             InnerMessage::ShowContextMenu(position) => {
                 self.context_menu_position = Some(position);
                 Action::None
@@ -384,6 +461,8 @@ struct State<R: iced::advanced::text::Renderer> {
     last_widget_width: f32,
     last_widget_height: f32,
     last_id: Option<Id>,
+    style_generation: usize,
+    modifiers: iced::keyboard::Modifiers,
 }
 
 const CHAR_WIDTH: f32 = 0.6;
@@ -430,6 +509,8 @@ where
             last_id: None,
             last_widget_height: 0.0,
             last_widget_width: 0.0,
+            style_generation: self.term.style_generation,
+            modifiers: iced::keyboard::Modifiers::default(),
         })
     }
 
@@ -494,14 +575,15 @@ where
 
                     let target_line_count = (widget_height / line_height.0) as usize;
                     let target_col_count = (widget_width / char_width.0) as usize;
-                    let size = self.term.grid.get_size();
-
-                    if size.rows != target_line_count || size.cols != target_col_count {
-                        let size = crate::terminal_grid::Size {
-                            cols: target_col_count,
-                            rows: target_line_count,
-                        };
-                        shell.publish(InnerMessage::Resize(size));
+                    let target_size = crate::terminal_grid::Size {
+                        cols: target_col_count,
+                        rows: target_line_count,
+                        pixel_width: (target_col_count as f32 * char_width.0) as usize,
+                        pixel_height: (target_line_count as f32 * line_height.0) as usize,
+                    };
+
+                    if !self.term.fixed_size && self.term.grid.get_size() != target_size {
+                        shell.publish(InnerMessage::Resize(target_size));
                     }
                 }
 
@@ -548,7 +630,17 @@ where
                             if let Some(char_pos) =
                                 self.screen_to_visible_position(cursor_position, layout, renderer)
                             {
-                                shell.publish(InnerMessage::StartSelection(char_pos));
+                                let link = state
+                                    .modifiers
+                                    .command()
+                                    .then(|| self.term.grid.hyperlink_at(char_pos.clone()))
+                                    .flatten();
+
+                                if let Some(url) = link {
+                                    shell.publish(InnerMessage::OpenUrl(url));
+                                } else {
+                                    shell.publish(InnerMessage::StartSelection(char_pos));
+                                }
                             }
                         }
                     }
@@ -573,6 +665,22 @@ where
                         shell.publish(InnerMessage::MoveSelection(char_pos));
                     }
                     shell.capture_event();
+                } else {
+                    let char_pos = cursor.position_over(layout.bounds()).and_then(|position| {
+                        self.screen_to_visible_position(position, layout, renderer)
+                    });
+                    let link = char_pos
+                        .clone()
+                        .and_then(|char_pos| self.term.grid.hyperlink_at(char_pos));
+
+                    if link.as_deref() != self.term.grid.hovered_hyperlink() {
+                        shell.publish(InnerMessage::Hover(char_pos));
+                    }
+                }
+            }
+            iced::Event::Mouse(iced::mouse::Event::CursorLeft) => {
+                if self.term.grid.hovered_hyperlink().is_some() {
+                    shell.publish(InnerMessage::Hover(None));
                 }
             }
             iced::Event::Mouse(iced::mouse::Event::ButtonReleased(button)) => {
@@ -620,6 +728,10 @@ where
                     shell.capture_event();
                 }
             }
+            iced::Event::Keyboard(iced::keyboard::Event::ModifiersChanged(modifiers)) => {
+                let state = state.state.downcast_mut::<State<Renderer>>();
+                state.modifiers = *modifiers;
+            }
             iced::Event::Window(iced::window::Event::Focused) => {
                 let state = state.state.downcast_mut::<State<Renderer>>();
                 state.focus();
@@ -629,6 +741,21 @@ where
         }
     }
 
+    fn mouse_interaction(
+        &self,
+        _tree: &iced::advanced::widget::Tree,
+        layout: iced::advanced::Layout<'_>,
+        cursor: iced::advanced::mouse::Cursor,
+        _viewport: &iced::Rectangle,
+        _renderer: &Renderer,
+    ) -> iced::advanced::mouse::Interaction {
+        if cursor.is_over(layout.bounds()) && self.term.grid.hovered_hyperlink().is_some() {
+            iced::advanced::mouse::Interaction::Pointer
+        } else {
+            iced::advanced::mouse::Interaction::default()
+        }
+    }
+
     fn layout(
         &mut self,
         tree: &mut iced::advanced::widget::Tree,
@@ -637,6 +764,11 @@ where
     ) -> iced::advanced::layout::Node {
         let state = tree.state.downcast_mut::<State<Renderer>>();
 
+        if state.style_generation != self.term.style_generation {
+            state.style_generation = self.term.style_generation;
+            state.prerenderer.set_style(self.term.style.clone());
+        }
+
         state.prerenderer.update(&self.term.grid, renderer);
 
         iced::advanced::layout::Node::new(limits.max())
@@ -678,13 +810,41 @@ where
 
         let y_multiplier = self.term.style.line_height.to_absolute(size).0;
 
+        // drawn in a layer above the text once all rows are done
+        let mut lines = Vec::new();
+
         // drawing text background
         for (row_index, render_data) in state.prerenderer.visible_rows().enumerate() {
-            let Some((paragraph, spans)) = render_data else {
+            let Some(RenderedRow {
+                paragraph,
+                spans,
+                decorations,
+            }) = render_data
+            else {
                 continue;
             };
             let y_offset = y_multiplier * row_index as f32;
 
+            for (index, decoration) in decorations.iter().enumerate() {
+                if decoration.underline.is_none() && !decoration.overline {
+                    continue;
+                }
+
+                let color = decoration
+                    .underline_color
+                    .unwrap_or(self.term.style.foreground_color);
+                for bounds in paragraph.span_bounds(index) {
+                    let position = bounds.position() + translation + Vector::new(0.0, y_offset);
+                    decoration_lines(
+                        decoration,
+                        Rectangle::new(position, bounds.size()),
+                        size.0,
+                        color,
+                        &mut lines,
+                    );
+                }
+            }
+
             for (index, span) in spans.iter().enumerate() {
                 if let Some(highlight) = span.highlight {
                     let regions = paragraph.span_bounds(index);
@@ -718,10 +878,79 @@ where
             );
         }
 
+        if !lines.is_empty() {
+            renderer.with_layer(bounds, |renderer| {
+                for (line, color) in lines {
+                    renderer.fill_quad(
+                        iced::advanced::renderer::Quad {
+                            bounds: line,
+                            ..Default::default()
+                        },
+                        color,
+                    );
+                }
+            });
+        }
+
         self.draw_cursor(renderer, &state, translation);
     }
 }
 
+/// Computes the rectangles making up the underline and overline of a span.
+fn decoration_lines(
+    decoration: &Decoration,
+    bounds: Rectangle,
+    text_size: f32,
+    color: iced::Color,
+    lines: &mut Vec<(Rectangle, iced::Color)>,
+) {
+    let thickness = (text_size / 14.0).max(1.0).round();
+    // the glyphs are centered vertically within the line
+    let center = bounds.center_y();
+    let underline = (center + text_size * 0.45).round();
+    let end = bounds.x + bounds.width;
+
+    // x, y and width of each segment
+    let mut segments = Vec::new();
+    // alternates between `on` pixels of line and `off` pixels of gap, `rise` moves every other segment up
+    let mut pattern = |y: f32, on: f32, off: f32, rise: f32| {
+        let mut x = bounds.x;
+        let mut raised = false;
+        while x < end {
+            let y = if raised { y - rise } else { y };
+            segments.push((x, y, on.min(end - x)));
+            x += on + off;
+            raised = !raised;
+        }
+    };
+
+    match decoration.underline {
+        Some(UnderlineStyle::Single) => pattern(underline, bounds.width, 0.0, 0.0),
+        Some(UnderlineStyle::Double) => {
+            pattern(underline - thickness, bounds.width, 0.0, 0.0);
+            pattern(underline + thickness, bounds.width, 0.0, 0.0);
+        }
+        Some(UnderlineStyle::Dotted) => pattern(underline, thickness, thickness, 0.0),
+        Some(UnderlineStyle::Dashed) => pattern(underline, thickness * 3.0, thickness * 2.0, 0.0),
+        // a zigzag is close enough at the usual text sizes
+        Some(UnderlineStyle::Curly) => {
+            pattern(underline + thickness, thickness * 2.0, 0.0, thickness * 2.0)
+        }
+        None => {}
+    }
+
+    if decoration.overline {
+        pattern((center - text_size * 0.6).round(), bounds.width, 0.0, 0.0);
+    }
+
+    lines.extend(segments.into_iter().map(|(x, y, width)| {
+        (
+            Rectangle::new(iced::Point::new(x, y), Size::new(width, thickness)),
+            color,
+        )
+    }));
+}
+
 impl<'a> TerminalWidget<'a> {
     pub fn new(term: &'a Terminal) -> Self {
         Self { term }
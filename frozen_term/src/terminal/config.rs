@@ -0,0 +1,53 @@
+/// Settings of the terminal emulation, as opposed to the [`Style`](crate::Style) it is drawn with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TerminalConfig {
+    /// Number of lines kept after they scrolled out of view.
+    pub scrollback_lines: usize,
+    /// The Unicode version which decides how many cells a character takes up, e.g. for emoji.
+    pub unicode_version: u8,
+    /// Characters of ambiguous width, like some CJK punctuation, take up two cells.
+    pub ambiguous_wide: bool,
+    /// Sent in reply to an enquiry (`ENQ`).
+    pub answerback: String,
+    /// Allows applications to enable the kitty keyboard protocol.
+    pub kitty_keyboard: bool,
+}
+
+impl Default for TerminalConfig {
+    fn default() -> Self {
+        Self {
+            scrollback_lines: 3500,
+            unicode_version: 9,
+            ambiguous_wide: false,
+            answerback: String::new(),
+            kitty_keyboard: false,
+        }
+    }
+}
+
+impl TerminalConfig {
+    pub fn scrollback_lines(mut self, lines: usize) -> Self {
+        self.scrollback_lines = lines;
+        self
+    }
+
+    pub fn unicode_version(mut self, version: u8) -> Self {
+        self.unicode_version = version;
+        self
+    }
+
+    pub fn ambiguous_wide(mut self, wide: bool) -> Self {
+        self.ambiguous_wide = wide;
+        self
+    }
+
+    pub fn answerback(mut self, answerback: impl Into<String>) -> Self {
+        self.answerback = answerback.into();
+        self
+    }
+
+    pub fn kitty_keyboard(mut self, enabled: bool) -> Self {
+        self.kitty_keyboard = enabled;
+        self
+    }
+}
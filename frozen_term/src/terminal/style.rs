@@ -1,6 +1,6 @@
 use std::{cell::LazyCell, sync::Arc};
 
-use termwiz::color::ColorAttribute;
+use termwiz::color::{ColorAttribute, SrgbaTuple};
 use wezterm_term::color::ColorPalette;
 
 use iced::{Padding, Pixels};
@@ -18,6 +18,11 @@ pub struct Style {
     /// If you use a custom font, you might have to experiment which value works best for your font.
     // pub font_height_modifier: f32,
     pub palette: Arc<Palette256>,
+    /// Shows bold text in the first 8 palette colors with their bright variant, which many color schemes expect.
+    pub bold_is_bright: bool,
+    /// The minimum contrast ratio between text and its background, from `1.0` (off) to `21.0` (black on white).
+    /// Values outside of that range are clamped.
+    pub minimum_contrast: f32,
 }
 
 pub struct Palette256(pub [iced::Color; 256]);
@@ -40,6 +45,25 @@ impl Palette256 {
 
         Self(iced_palette)
     }
+
+    fn to_wezterm(&self) -> wezterm_term::color::Palette256 {
+        let mut wez_palette = [SrgbaTuple(0.0, 0.0, 0.0, 1.0); 256];
+
+        for (iced_color, wez_color) in self.0.iter().zip(wez_palette.iter_mut()) {
+            *wez_color = to_srgba(*iced_color);
+        }
+
+        wezterm_term::color::Palette256(wez_palette)
+    }
+}
+
+fn to_srgba(color: iced::Color) -> SrgbaTuple {
+    SrgbaTuple(color.r, color.g, color.b, color.a)
+}
+
+fn from_srgba(color: SrgbaTuple) -> iced::Color {
+    let (r, g, b, a) = color.to_tuple_rgba();
+    iced::Color::from_rgba(r, g, b, a)
 }
 
 const DEFAULT_STYLE: LazyCell<Style> = LazyCell::new(|| {
@@ -61,6 +85,8 @@ const DEFAULT_STYLE: LazyCell<Style> = LazyCell::new(|| {
         font: iced::Font::MONOSPACE,
         // font_height_modifier: 1.0,
         palette: Arc::new(Palette256::from_wezterm(palette.colors)),
+        bold_is_bright: false,
+        minimum_contrast: 1.0,
     }
 });
 
@@ -111,6 +137,89 @@ impl Style {
         self
     }
 
+    pub fn bold_is_bright(mut self, bold_is_bright: bool) -> Self {
+        self.bold_is_bright = bold_is_bright;
+        self
+    }
+
+    /// Text colors below the contrast ratio are moved towards black or white.
+    pub fn minimum_contrast(mut self, ratio: f32) -> Self {
+        self.minimum_contrast = ratio;
+        self
+    }
+
+    /// The palette the emulator reports to applications which query colors.
+    pub(crate) fn to_wezterm_palette(&self) -> ColorPalette {
+        let foreground = to_srgba(self.foreground_color);
+        let background = to_srgba(self.background_color);
+
+        ColorPalette {
+            colors: self.palette.to_wezterm(),
+            foreground,
+            background,
+            cursor_fg: background,
+            cursor_bg: foreground,
+            cursor_border: foreground,
+            ..ColorPalette::default()
+        }
+    }
+
+    /// Takes over the colors of a palette which applications may have changed.
+    pub(crate) fn with_wezterm_palette(mut self, palette: &ColorPalette) -> Self {
+        self.foreground_color = from_srgba(palette.foreground);
+        self.background_color = from_srgba(palette.background);
+        self.palette = Arc::new(Palette256::from_wezterm(palette.colors.clone()));
+        self
+    }
+
+    /// Resolves the text color of a cell, which depends on its intensity if `bold_is_bright` is set.
+    pub(crate) fn get_foreground_color(
+        &self,
+        color: ColorAttribute,
+        bold: bool,
+    ) -> Option<iced::Color> {
+        match color {
+            ColorAttribute::PaletteIndex(index) if bold && self.bold_is_bright && index < 8 => {
+                self.get_color(ColorAttribute::PaletteIndex(index + 8))
+            }
+            color => self.get_color(color),
+        }
+    }
+
+    /// Moves the text color towards black or white until it reaches the minimum contrast.
+    pub(crate) fn ensure_contrast(
+        &self,
+        foreground: iced::Color,
+        background: iced::Color,
+    ) -> iced::Color {
+        let minimum = self.minimum_contrast.clamp(1.0, 21.0);
+        if minimum <= 1.0 || contrast_ratio(foreground, background) >= minimum {
+            return foreground;
+        }
+
+        // the direction with more room for contrast
+        let target = if contrast_ratio(iced::Color::BLACK, background)
+            > contrast_ratio(iced::Color::WHITE, background)
+        {
+            iced::Color::BLACK
+        } else {
+            iced::Color::WHITE
+        };
+
+        // the smallest change which is enough, or the target if even that isn't
+        let (mut low, mut high) = (0.0, 1.0);
+        for _ in 0..8 {
+            let amount = (low + high) / 2.0;
+            if contrast_ratio(mix(foreground, target, amount), background) >= minimum {
+                high = amount;
+            } else {
+                low = amount;
+            }
+        }
+
+        mix(foreground, target, high)
+    }
+
     pub(crate) fn get_color(&self, color: ColorAttribute) -> Option<iced::Color> {
         match color {
             ColorAttribute::TrueColorWithPaletteFallback(srgba_tuple, _)
@@ -123,3 +232,115 @@ impl Style {
         }
     }
 }
+
+/// Blends `color` towards `base`, `amount` of `1.0` returns `base`.
+pub(crate) fn mix(color: iced::Color, base: iced::Color, amount: f32) -> iced::Color {
+    iced::Color {
+        r: color.r + (base.r - color.r) * amount,
+        g: color.g + (base.g - color.g) * amount,
+        b: color.b + (base.b - color.b) * amount,
+        a: color.a,
+    }
+}
+
+/// The contrast ratio as defined by WCAG, between `1.0` and `21.0`.
+fn contrast_ratio(a: iced::Color, b: iced::Color) -> f32 {
+    let (a, b) = (relative_luminance(a), relative_luminance(b));
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+fn relative_luminance(color: iced::Color) -> f32 {
+    let linear = |channel: f32| {
+        if channel <= 0.04045 {
+            channel / 12.92
+        } else {
+            ((channel + 0.055) / 1.055).powf(2.4)
+        }
+    };
+
+    0.2126 * linear(color.r) + 0.7152 * linear(color.g) + 0.0722 * linear(color.b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gray(value: f32) -> iced::Color {
+        iced::Color::from_rgb(value, value, value)
+    }
+
+    #[test]
+    fn wcag_contrast_ratio() {
+        let ratio = contrast_ratio(iced::Color::BLACK, iced::Color::WHITE);
+        assert!((ratio - 21.0).abs() < 0.01, "{ratio}");
+
+        let ratio = contrast_ratio(iced::Color::WHITE, iced::Color::BLACK);
+        assert!((ratio - 21.0).abs() < 0.01, "{ratio}");
+
+        let ratio = contrast_ratio(gray(0.5), gray(0.5));
+        assert!((ratio - 1.0).abs() < 0.001, "{ratio}");
+
+        // #777777 on white, the usual example for just missing 4.5
+        let ratio = contrast_ratio(gray(0x77 as f32 / 255.0), iced::Color::WHITE);
+        assert!((ratio - 4.48).abs() < 0.01, "{ratio}");
+    }
+
+    #[test]
+    fn minimum_contrast_is_clamped() {
+        let foreground = gray(0.5);
+        let background = gray(0.9);
+
+        let style = Style {
+            minimum_contrast: 0.0,
+            ..Style::default()
+        };
+        assert_eq!(style.ensure_contrast(foreground, background), foreground);
+
+        let style = Style {
+            minimum_contrast: 100.0,
+            ..Style::default()
+        };
+        assert_eq!(
+            style.ensure_contrast(foreground, background),
+            iced::Color::BLACK
+        );
+    }
+
+    #[test]
+    fn ensure_contrast() {
+        let background = gray(0.1);
+        let foreground = gray(0.15);
+
+        let style = Style::default();
+        assert_eq!(style.ensure_contrast(foreground, background), foreground);
+
+        for minimum in [3.0, 4.5, 7.0] {
+            let style = Style::default().minimum_contrast(minimum);
+            let readable = style.ensure_contrast(foreground, background);
+            let ratio = contrast_ratio(readable, background);
+            // moved towards white, but not much further than needed
+            assert!(readable.r > foreground.r);
+            assert!(
+                ratio >= minimum && ratio < minimum + 0.2,
+                "{minimum}: {ratio}"
+            );
+        }
+
+        // text which already has enough contrast is kept
+        let style = Style::default().minimum_contrast(4.5);
+        assert_eq!(
+            style.ensure_contrast(iced::Color::WHITE, background),
+            iced::Color::WHITE
+        );
+
+        // a light background moves the text towards black
+        let readable = style.ensure_contrast(gray(0.8), iced::Color::WHITE);
+        assert!(readable.r < 0.8);
+        assert!(contrast_ratio(readable, iced::Color::WHITE) >= 4.5);
+
+        // the maximum is only reached with black on white
+        let style = Style::default().minimum_contrast(21.0);
+        let readable = style.ensure_contrast(gray(0.5), gray(0.9));
+        assert_eq!(readable, iced::Color::BLACK);
+    }
+}
@@ -22,6 +22,12 @@ pub trait TerminalGrid {
     fn currently_selecting(&self) -> bool;
     fn selected_text(&self) -> Option<String>;
 
+    /// Returns the URL of the OSC 8 hyperlink at the position, if there is one.
+    fn hyperlink_at(&self, position: VisiblePosition) -> Option<String>;
+    /// Marks the hyperlink at the position as hovered, `None` when the mouse left the terminal.
+    fn hover(&mut self, position: Option<VisiblePosition>);
+    fn hovered_hyperlink(&self) -> Option<&str>;
+
     fn get_title(&self) -> &str;
     fn get_size(&self) -> Size;
     fn get_cursor(&self) -> Option<VisiblePosition>;
@@ -36,15 +42,45 @@ where
 
     fn clear_cache(&mut self);
     fn update(&mut self, grid: &Self::Grid, renderer: &R);
-    fn visible_rows<'a>(
-        &'a self,
-    ) -> impl Iterator<Item = Option<(&'a R::Paragraph, &'a [text::Span<'a, (), R::Font>])>>;
+    fn visible_rows<'a>(&'a self) -> impl Iterator<Item = Option<RenderedRow<'a, R>>>;
+}
+
+/// A row ready to be drawn, the decorations belong to the span with the same index.
+pub struct RenderedRow<'a, R>
+where
+    R: text::Renderer,
+    R::Font: 'static,
+{
+    pub paragraph: &'a R::Paragraph,
+    pub spans: &'a [text::Span<'a, (), R::Font>],
+    pub decorations: &'a [Decoration],
+}
+
+/// Lines drawn by the widget itself, as text spans only support a plain underline.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Decoration {
+    pub underline: Option<UnderlineStyle>,
+    pub underline_color: Option<iced::Color>,
+    pub overline: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnderlineStyle {
+    Single,
+    Double,
+    Curly,
+    Dotted,
+    Dashed,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Size {
     pub cols: usize,
     pub rows: usize,
+    /// Width of the text area in pixels
+    pub pixel_width: usize,
+    /// Height of the text area in pixels
+    pub pixel_height: usize,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -1,11 +1,20 @@
-use std::{ops::Range, sync::Arc};
+use std::{
+    ops::Range,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
 
-use termwiz::surface::CursorVisibility;
+use termwiz::{cell::UnicodeVersion, hyperlink::Hyperlink, surface::CursorVisibility};
 use tokio::sync::mpsc;
 use tokio_stream::Stream;
-use wezterm_term::{PhysRowIndex, TerminalConfiguration, TerminalSize, color::ColorPalette};
+use wezterm_term::{
+    Alert, AlertHandler, PhysRowIndex, TerminalConfiguration, TerminalSize, color::ColorPalette,
+};
 
 use crate::{
+    TerminalConfig,
     terminal_grid::{Size, TerminalGrid, VisiblePosition},
     wezterm::selection::{SelectionPosition, SelectionState, is_selected},
 };
@@ -31,12 +40,51 @@ impl std::io::Write for BridgedWriter {
     }
 }
 
+/// Counts palette changes made by applications, e.g. with OSC 4, 10, 11 or 104.
+struct PaletteChanges(Arc<AtomicUsize>);
+
+impl AlertHandler for PaletteChanges {
+    fn alert(&mut self, alert: Alert) {
+        if let Alert::PaletteChanged = alert {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
 #[derive(Debug)]
-pub struct Config {}
+pub struct Config {
+    config: TerminalConfig,
+    palette: ColorPalette,
+    // tells the terminal that the configuration changed
+    generation: usize,
+}
 
 impl TerminalConfiguration for Config {
+    fn generation(&self) -> usize {
+        self.generation
+    }
+
+    fn scrollback_size(&self) -> usize {
+        self.config.scrollback_lines
+    }
+
+    fn unicode_version(&self) -> UnicodeVersion {
+        UnicodeVersion {
+            version: self.config.unicode_version,
+            ambiguous_are_wide: self.config.ambiguous_wide,
+        }
+    }
+
+    fn enq_answerback(&self) -> String {
+        self.config.answerback.clone()
+    }
+
+    fn enable_kitty_keyboard(&self) -> bool {
+        self.config.kitty_keyboard
+    }
+
     fn color_palette(&self) -> wezterm_term::color::ColorPalette {
-        ColorPalette::default()
+        self.palette.clone()
     }
 }
 
@@ -45,6 +93,11 @@ pub struct WeztermGrid {
     scroll_offset: usize,
     size: Size,
     selection: SelectionState,
+    config: TerminalConfig,
+    palette: ColorPalette,
+    config_generation: usize,
+    palette_changes: Arc<AtomicUsize>,
+    hovered_link: Option<Arc<Hyperlink>>,
 }
 
 impl WeztermGrid {
@@ -53,30 +106,75 @@ impl WeztermGrid {
         let size = Size {
             rows: term_size.rows,
             cols: term_size.cols,
+            pixel_width: term_size.pixel_width,
+            pixel_height: term_size.pixel_height,
         };
 
         let (send, recv) = mpsc::channel(100);
         let recv = tokio_stream::wrappers::ReceiverStream::new(recv);
 
-        let term = wezterm_term::Terminal::new(
+        let mut term = wezterm_term::Terminal::new(
             term_size,
-            Arc::new(Config {}),
+            Arc::new(Config {
+                config: TerminalConfig::default(),
+                palette: ColorPalette::default(),
+                generation: 0,
+            }),
             "frozen_term",
             env!("CARGO_PKG_VERSION"),
             Box::new(BridgedWriter { send }),
         );
 
+        let palette_changes = Arc::new(AtomicUsize::new(0));
+        term.set_notification_handler(Box::new(PaletteChanges(palette_changes.clone())));
+
         (
             Self {
                 terminal: term,
                 scroll_offset: 0,
                 selection: SelectionState::new(),
                 size,
+                config: TerminalConfig::default(),
+                palette: ColorPalette::default(),
+                config_generation: 0,
+                palette_changes,
+                hovered_link: None,
             },
             recv,
         )
     }
 
+    pub fn set_config(&mut self, config: TerminalConfig) {
+        self.config = config;
+        self.apply_config();
+        self.update_scroll(self.scroll_offset);
+    }
+
+    /// Sets the palette applications see, unless they changed it themselves.
+    pub fn set_palette(&mut self, palette: ColorPalette) {
+        self.palette = palette;
+        self.apply_config();
+    }
+
+    fn apply_config(&mut self) {
+        self.config_generation += 1;
+        self.terminal.set_config(Arc::new(Config {
+            config: self.config.clone(),
+            palette: self.palette.clone(),
+            generation: self.config_generation,
+        }));
+    }
+
+    /// The palette including the changes made by applications.
+    pub fn palette(&self) -> ColorPalette {
+        self.terminal.palette()
+    }
+
+    /// Increases whenever an application changed the palette.
+    pub fn palette_changes(&self) -> usize {
+        self.palette_changes.load(Ordering::Relaxed)
+    }
+
     fn invalidate_lines(&mut self, mut invalidate: Range<PhysRowIndex>) {
         self.terminal.increment_seqno();
         let seqno = self.terminal.current_seqno();
@@ -117,6 +215,17 @@ impl WeztermGrid {
         }
     }
 
+    fn link_at(&self, position: VisiblePosition) -> Option<Arc<Hyperlink>> {
+        let y = self.scroll_offset + position.y;
+        let line = self.screen_lines(y..y + 1).into_iter().next()?;
+
+        line.visible_cells()
+            .find(|cell| {
+                (cell.cell_index()..cell.cell_index() + cell.width()).contains(&position.x)
+            })
+            .and_then(|cell| cell.attrs().hyperlink().cloned())
+    }
+
     fn screen_lines(&self, range: Range<usize>) -> Vec<wezterm_term::Line> {
         let screen = self.terminal.screen();
         let range = screen.stable_range(&(range.start as isize..range.end as isize));
@@ -147,6 +256,8 @@ impl TerminalGrid for WeztermGrid {
         self.terminal.resize(TerminalSize {
             cols: size.cols,
             rows: size.rows,
+            pixel_width: size.pixel_width,
+            pixel_height: size.pixel_height,
             ..Default::default()
         });
         self.size = size;
@@ -250,6 +361,25 @@ impl TerminalGrid for WeztermGrid {
         }
     }
 
+    fn hyperlink_at(&self, position: VisiblePosition) -> Option<String> {
+        self.link_at(position).map(|link| link.uri().to_string())
+    }
+
+    fn hover(&mut self, position: Option<VisiblePosition>) {
+        let link = position.and_then(|position| self.link_at(position));
+        if link == self.hovered_link {
+            return;
+        }
+
+        self.hovered_link = link;
+        // the link can span several lines
+        self.invalidate_lines(self.scroll_offset..self.scroll_offset + self.size.rows);
+    }
+
+    fn hovered_hyperlink(&self) -> Option<&str> {
+        self.hovered_link.as_ref().map(|link| link.uri())
+    }
+
     fn get_title(&self) -> &str {
         self.terminal.get_title()
     }
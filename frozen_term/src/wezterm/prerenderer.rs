@@ -1,11 +1,17 @@
 use std::{collections::VecDeque, fmt::Debug, ops::Range};
 
-use iced::{advanced::text, widget::text::Span};
+use iced::{
+    advanced::text,
+    font::{Style as FontStyle, Weight},
+    widget::text::Span,
+};
+use termwiz::{cell::Intensity, hyperlink::Hyperlink};
 use wezterm_term::{CellAttributes, PhysRowIndex, Underline};
 
 use crate::{
     Style,
-    terminal_grid::PreRenderer,
+    terminal::style::mix,
+    terminal_grid::{Decoration, PreRenderer, RenderedRow, UnderlineStyle},
     wezterm::{
         WeztermGrid,
         selection::{SelectionPosition, is_maybe_selected},
@@ -30,6 +36,14 @@ impl<R: text::Renderer> WeztermPreRenderer<R> {
             visible_cache_range: 0..0,
         }
     }
+
+    /// Replaces the style, all rows are rendered again.
+    pub(crate) fn set_style(&mut self, style: Style) {
+        self.style = style;
+        self.cache_rows.clear();
+        self.row_cache_start = 0;
+        self.visible_cache_range = 0..0;
+    }
 }
 
 impl<R> PreRenderer<R> for WeztermPreRenderer<R>
@@ -52,6 +66,7 @@ where
         let range = grid.scroll_offset..grid.scroll_offset + screen.physical_rows;
 
         let selection = grid.selection.get_selection();
+        let hovered_link = grid.hovered_link.as_deref();
 
         let text_size = self
             .style
@@ -116,6 +131,7 @@ where
             let mut current_text = String::new();
             let mut current_attrs = CellAttributes::default();
             let mut spans: Vec<Span<(), R::Font>> = Vec::new();
+            let mut decorations = Vec::new();
 
             for (cell_index, cell) in line.visible_cells().enumerate() {
                 let cell_selected = is_maybe_selected(
@@ -129,9 +145,11 @@ where
                     push_span(
                         &self.style,
                         &mut spans,
+                        &mut decorations,
                         current_text,
                         current_attrs,
                         is_current_selected,
+                        hovered_link,
                     );
                     current_attrs = cell.attrs().clone();
                     is_current_selected = cell_selected;
@@ -144,9 +162,11 @@ where
             push_span(
                 &self.style,
                 &mut spans,
+                &mut decorations,
                 current_text,
                 current_attrs,
                 is_current_selected,
+                hovered_link,
             );
 
             let cached = if !spans.is_empty() {
@@ -164,7 +184,7 @@ where
                     hint_factor: None,
                 };
                 let paragraph = iced::advanced::text::Paragraph::with_spans(text);
-                Some((paragraph, spans))
+                Some((paragraph, spans, decorations))
             } else {
                 None
             };
@@ -174,15 +194,15 @@ where
         }
     }
 
-    fn visible_rows<'a>(
-        &'a self,
-    ) -> impl Iterator<Item = Option<(&'a R::Paragraph, &'a [text::Span<'a, (), R::Font>])>> {
+    fn visible_rows<'a>(&'a self) -> impl Iterator<Item = Option<RenderedRow<'a, R>>> {
         self.cache_rows
             .range(self.visible_cache_range.clone())
             .map(|row| {
-                row.cached
-                    .as_ref()
-                    .map(|cached| (&cached.0, cached.1.as_slice()))
+                row.cached.as_ref().map(|cached| RenderedRow {
+                    paragraph: &cached.0,
+                    spans: cached.1.as_slice(),
+                    decorations: cached.2.as_slice(),
+                })
             })
     }
 }
@@ -190,16 +210,21 @@ where
 fn push_span<Font>(
     style: &Style,
     spans: &mut Vec<Span<(), Font>>,
+    decorations: &mut Vec<Decoration>,
     text: String,
     attributes: CellAttributes,
     is_current_selected: bool,
-) {
+    hovered_link: Option<&Hyperlink>,
+) where
+    Font: From<iced::Font>,
+{
     if text.is_empty() {
         return;
     }
 
+    let bold = attributes.intensity() == Intensity::Bold;
     let mut background = style.get_color(attributes.background());
-    let mut foreground = style.get_color(attributes.foreground());
+    let mut foreground = style.get_foreground_color(attributes.foreground(), bold);
 
     // Apply reverse colors for original cell attributes
     if attributes.reverse() != is_current_selected {
@@ -212,18 +237,65 @@ fn push_span<Font>(
         }
     }
 
+    if attributes.intensity() == Intensity::Half {
+        let base = background.unwrap_or(style.background_color);
+        foreground = Some(mix(foreground.unwrap_or(style.foreground_color), base, 0.5));
+    }
+
+    // after dimming, which would undo it otherwise
+    let text_color = foreground.unwrap_or(style.foreground_color);
+    let readable = style.ensure_contrast(text_color, background.unwrap_or(style.background_color));
+    if readable != text_color {
+        foreground = Some(readable);
+    }
+
+    if attributes.invisible() {
+        foreground = Some(iced::Color::TRANSPARENT);
+    }
+
+    let mut font = style.font;
+    if bold {
+        font.weight = Weight::Bold;
+    }
+    if attributes.italic() {
+        font.style = FontStyle::Italic;
+    }
+
+    let underline = match attributes.underline() {
+        Underline::None => None,
+        Underline::Single => Some(UnderlineStyle::Single),
+        Underline::Double => Some(UnderlineStyle::Double),
+        Underline::Curly => Some(UnderlineStyle::Curly),
+        Underline::Dotted => Some(UnderlineStyle::Dotted),
+        Underline::Dashed => Some(UnderlineStyle::Dashed),
+    };
+
+    // hovered links are underlined, like in a browser
+    let hovered = attributes
+        .hyperlink()
+        .is_some_and(|link| Some(link.as_ref()) == hovered_link);
+    let underline = underline.or(hovered.then_some(UnderlineStyle::Single));
+
     let span = iced::advanced::text::Span::new(text)
         .color_maybe(foreground)
         .background_maybe(background)
-        .underline(attributes.underline() != Underline::None);
+        .font_maybe((font != style.font).then_some(font))
+        .strikethrough(attributes.strikethrough());
 
     spans.push(span);
+    decorations.push(Decoration {
+        underline,
+        // the text color is used if there is none
+        underline_color: style.get_color(attributes.underline_color()).or(foreground),
+        overline: attributes.overline(),
+    });
 }
 
 pub struct ParagraphRow<R: text::Renderer> {
     pub cached: Option<(
         R::Paragraph,
         Vec<iced::advanced::text::Span<'static, (), R::Font>>,
+        Vec<Decoration>,
     )>,
     // pub paragraph: R::Paragraph,
     // pub spans: Vec<iced::advanced::text::Span<'static, (), R::Font>>,